@@ -7,6 +7,7 @@
 use error_chain::ChainedError;
 use futures::Future;
 use intecture_api;
+use intecture_api::errors::serializable;
 use std::{convert, error, io};
 
 error_chain! {
@@ -21,6 +22,14 @@ impl convert::From<Error> for io::Error {
     }
 }
 
+// The agent's own errors only make sense to the agent, so they're sent to
+// clients as plain messages.
+impl convert::From<Error> for serializable::Error {
+    fn from(e: Error) -> serializable::Error {
+        format!("{}", e.display_chain()).into()
+    }
+}
+
 // @todo This should disappear once Futures are officially supported
 // by error_chain.
 // See: https://github.com/rust-lang-nursery/error-chain/issues/90
@@ -9,57 +9,95 @@ extern crate env_logger;
 #[macro_use] extern crate error_chain;
 extern crate futures;
 extern crate intecture_api;
+#[macro_use] extern crate log;
+extern crate openssl;
 #[macro_use] extern crate serde_derive;
 extern crate serde_json;
 extern crate tokio_core;
+extern crate tokio_file_unix;
+extern crate tokio_io;
+extern crate tokio_openssl;
 extern crate tokio_proto;
 extern crate tokio_service;
+extern crate tokio_uds;
 extern crate toml;
 
 mod errors;
+mod policy;
+mod stdio;
 
-use error_chain::ChainedError;
 use errors::*;
-use futures::{future, Future};
+use futures::{future, Future, Stream};
+use intecture_api::errors::serializable;
+use intecture_api::host::handshake;
 use intecture_api::host::local::Local;
-use intecture_api::host::remote::{JsonLineProto, LineMessage};
+use intecture_api::host::remote::{Encoding, WireMessage, WireProto};
 use intecture_api::remote::{Executable, Request, ResponseResult};
-use std::fs::File;
+use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod, SslVerifyMode};
+use std::fs::{self, File};
 use std::io::{self, Read};
 use std::net::SocketAddr;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use policy::Policy;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio_core::reactor::Remote;
+use stdio::Stdio;
+use tokio_core::net::TcpListener;
+use tokio_core::reactor::{Core, Handle};
+use tokio_openssl::SslAcceptorExt;
+use tokio_proto::BindServer;
 use tokio_proto::streaming::Message;
-use tokio_proto::TcpServer;
-use tokio_service::{NewService, Service};
+use tokio_service::Service;
+use tokio_uds::UnixListener;
 
+/// Default file mode for the agent's Unix socket, which grants access to
+/// the agent's user only.
+const DEFAULT_SOCKET_MODE: u32 = 0o600;
+
+#[derive(Clone)]
 pub struct Api {
     host: Local,
+    policy: Arc<Policy>,
 }
 
-pub struct NewApi {
-    remote: Remote,
+impl Api {
+    // Each connection gets its own session, so that clients can only control
+    // the commands they started.
+    fn session(&self) -> Api {
+        Api {
+            host: self.host.session(),
+            policy: self.policy.clone(),
+        }
+    }
 }
 
 impl Service for Api {
-    type Request = LineMessage;
-    type Response = LineMessage;
-    type Error = Error;
+    type Request = WireMessage;
+    type Response = WireMessage;
+    type Error = io::Error;
     type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
 
-    fn call(&self, req: Self::Request) -> Self::Future {
-        let req = match req {
-            Message::WithBody(req, _) => req,
-            Message::WithoutBody(req) => req,
-        };
+    fn call(&self, mut req: Self::Request) -> Self::Future {
+        let body = req.take_body();
 
-        let request: Request = match serde_json::from_value(req).chain_err(|| "Could not deserialize Request") {
+        let request: Request = match serde_json::from_value(req.into_inner()).chain_err(|| "Could not deserialize Request") {
             Ok(r) => r,
             Err(e) => return Box::new(future::ok(error_to_msg(e))),
         };
 
+        if let Err(reason) = self.policy.check(&request) {
+            info!("Denied request: {}", reason);
+            return Box::new(future::ok(denied_to_msg(reason)));
+        }
+
+        // Errors are passed back untouched, so that the client sees the same
+        // error it would have if it ran the request locally.
+        let request = match body {
+            Some(b) => Message::WithBody(request, b),
+            None => Message::WithoutBody(request),
+        };
+
         Box::new(request.exec(&self.host)
-            .chain_err(|| "Failed to execute Request")
             .then(|req| {
                 match req {
                     Ok(mut msg) => {
@@ -78,28 +116,37 @@ impl Service for Api {
     }
 }
 
-impl NewService for NewApi {
-    type Request = LineMessage;
-    type Response = LineMessage;
-    type Error = Error;
-    type Instance = Api;
-    fn new_service(&self) -> io::Result<Self::Instance> {
-        // XXX Danger zone! If we're running multiple threads, this `unwrap()`
-        // will explode. The API requires a `Handle`, but we can only send a
-        // `Remote` to this Service. Currently we force the `Handle`, which is
-        // only safe for the current thread.
-        // See https://github.com/alexcrichton/tokio-process/issues/23
-        let handle = self.remote.handle().unwrap();
-
-        Ok(Api {
-            host: Local::new(&handle).wait().unwrap(),
-        })
-    }
+#[derive(Default, Deserialize)]
+struct Config {
+    address: Option<SocketAddr>,
+    socket: Option<SocketConfig>,
+    tls: Option<TlsConfig>,
+    /// Pre-shared token that clients must authenticate with
+    token: Option<String>,
+    /// Message encodings that clients may choose from
+    encodings: Option<Vec<Encoding>>,
+    /// Rules restricting which requests clients may make
+    #[serde(default)]
+    policy: Policy,
 }
 
 #[derive(Deserialize)]
-struct Config {
-    address: SocketAddr,
+struct SocketConfig {
+    /// Path to the Unix socket the agent will listen on
+    path: PathBuf,
+    /// Octal file mode for the socket, e.g. "0660"
+    mode: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TlsConfig {
+    /// Path to the agent's PEM encoded certificate
+    cert: PathBuf,
+    /// Path to the agent's PEM encoded private key
+    key: PathBuf,
+    /// Path to the PEM encoded CA certificate used to verify clients. If this
+    /// is set, clients must present a certificate signed by this CA.
+    ca: Option<PathBuf>,
 }
 
 quick_main!(|| -> Result<()> {
@@ -121,37 +168,187 @@ quick_main!(|| -> Result<()> {
                                 .value_name("ADDR")
                                 .help("Set the socket address this server will listen on (e.g. 0.0.0.0:7101)")
                                 .takes_value(true))
+                            .arg(clap::Arg::with_name("stdio")
+                                .long("stdio")
+                                .help("Serve a single session over stdin/stdout instead of listening on a socket"))
                             .group(clap::ArgGroup::with_name("config_or_else")
-                                .args(&["config", "addr"])
+                                .args(&["config", "addr", "stdio"])
+                                .multiple(true)
                                 .required(true))
                             .get_matches();
 
-    let config = if let Some(c) = matches.value_of("config") {
+    let mut config = if let Some(c) = matches.value_of("config") {
         let mut fh = File::open(c).chain_err(|| "Could not open config file")?;
         let mut buf = Vec::new();
         fh.read_to_end(&mut buf).chain_err(|| "Could not read config file")?;
         toml::from_slice(&buf).chain_err(|| "Config file contained invalid TOML")?
     } else {
-        let address = matches.value_of("addr").unwrap().parse().chain_err(|| "Invalid server address")?;
-        Config { address }
+        Config::default()
     };
 
-    // XXX We can only run a single thread here, or big boom!!
-    // The API requires a `Handle`, but we can only send a `Remote`.
-    // Currently we force the issue (`unwrap()`), which is only safe
-    // for the current thread.
-    // See https://github.com/alexcrichton/tokio-process/issues/23
-    let server = TcpServer::new(JsonLineProto, config.address);
-    server.with_handle(move |handle| {
-        Arc::new(NewApi {
-            remote: handle.remote().clone(),
-        })
-    });
+    if let Some(addr) = matches.value_of("addr") {
+        config.address = Some(addr.parse().chain_err(|| "Invalid server address")?);
+    }
+
+    let mut core = Core::new().chain_err(|| "Could not start event loop")?;
+    let handle = core.handle();
+    let host = core.run(Local::new(&handle)).chain_err(|| "Could not load local host")?;
+    let api = Api { host, policy: Arc::new(config.policy) };
+    let encodings = config.encodings.take().unwrap_or_else(|| vec![Encoding::Json, Encoding::Cbor]);
+
+    if matches.is_present("stdio") {
+        let (stdio, done) = Stdio::new(&handle).chain_err(|| "Could not open stdio")?;
+        let h = handle.clone();
+        handle.spawn(handshake::server(stdio, config.token.clone(), encodings)
+            .map(move |(io, encoding)| WireProto::new(encoding).bind_server(&h, io, api.session()))
+            .map_err(|e| warn!("Handshake failed: {}", e)));
+        // The receiver errors when the session ends and the transport is
+        // dropped, which is exactly what we're waiting for.
+        let _ = core.run(done);
+        return Ok(());
+    }
+
+    let mut servers = Vec::new();
+
+    if let Some(address) = config.address {
+        servers.push(serve_tcp(&handle, &api, &address, config.tls.as_ref(), config.token.clone(), &encodings)?);
+    }
+
+    if let Some(ref socket) = config.socket {
+        servers.push(serve_unix(&handle, &api, socket, config.token.clone(), &encodings)?);
+    }
+
+    if servers.is_empty() {
+        bail!("Nothing to listen on. Set an address or socket in your config file, or use --address.");
+    }
+
+    core.run(future::join_all(servers)).chain_err(|| "Server stopped unexpectedly")?;
     Ok(())
 });
 
-fn error_to_msg(e: Error) -> LineMessage {
-    let response = ResponseResult::Err(format!("{}", e.display_chain()));
+fn serve_tcp(handle: &Handle, api: &Api, address: &SocketAddr, tls: Option<&TlsConfig>, token: Option<String>, encodings: &[Encoding]) -> Result<Box<Future<Item = (), Error = Error>>> {
+    let acceptor = match tls {
+        Some(tls) => Some(tls_acceptor(tls)?),
+        None => None,
+    };
+
+    let listener = TcpListener::bind(address, handle)
+        .chain_err(|| "Could not bind to server address")?;
+    let handle = handle.clone();
+    let api = api.clone();
+    let encodings = encodings.to_vec();
+
+    Ok(Box::new(listener.incoming().for_each(move |(sock, addr)| {
+        debug!("Accepted connection from {}", addr);
+
+        let api = api.clone();
+        let encodings = encodings.clone();
+        let token = token.clone();
+        let h = handle.clone();
+
+        match acceptor {
+            Some(ref acceptor) => {
+                handle.spawn(acceptor.accept_async(sock)
+                    .map_err(|e| Error::with_chain(e, "TLS handshake failed"))
+                    .and_then(move |stream| handshake::server(stream, token, encodings).map_err(|e| e.into()))
+                    .map(move |(io, encoding)| WireProto::new(encoding).bind_server(&h, io, api.session()))
+                    .map_err(move |e| warn!("Handshake with {} failed: {}", addr, e)));
+            },
+            None => {
+                handle.spawn(handshake::server(sock, token, encodings)
+                    .map(move |(io, encoding)| WireProto::new(encoding).bind_server(&h, io, api.session()))
+                    .map_err(move |e| warn!("Handshake with {} failed: {}", addr, e)));
+            },
+        }
+
+        Ok(())
+    }).chain_err(|| "TCP listener failed")))
+}
+
+fn serve_unix(handle: &Handle, api: &Api, config: &SocketConfig, token: Option<String>, encodings: &[Encoding]) -> Result<Box<Future<Item = (), Error = Error>>> {
+    let mode = match config.mode {
+        Some(ref m) => u32::from_str_radix(m, 8).chain_err(|| "Invalid socket mode")?,
+        None => DEFAULT_SOCKET_MODE,
+    };
+
+    // Anyone who can write to the socket can control the agent, so don't let
+    // just anyone write to it.
+    if mode & 0o002 != 0 {
+        bail!("Socket mode {:o} would make the socket world writable", mode);
+    }
+
+    remove_stale_socket(&config.path)?;
+
+    let listener = UnixListener::bind(&config.path, handle)
+        .chain_err(|| "Could not bind to socket")?;
+    fs::set_permissions(&config.path, fs::Permissions::from_mode(mode))
+        .chain_err(|| "Could not set socket permissions")?;
+
+    let actual = fs::metadata(&config.path)
+        .chain_err(|| "Could not read socket permissions")?
+        .permissions()
+        .mode() & 0o777;
+    if actual != mode {
+        bail!("Socket has mode {:o}, expected {:o}", actual, mode);
+    }
+
+    let handle = handle.clone();
+    let api = api.clone();
+    let encodings = encodings.to_vec();
+
+    Ok(Box::new(listener.incoming().for_each(move |(sock, _)| {
+        debug!("Accepted connection on Unix socket");
+
+        let api = api.clone();
+        let h = handle.clone();
+
+        handle.spawn(handshake::server(sock, token.clone(), encodings.clone())
+            .map(move |(io, encoding)| WireProto::new(encoding).bind_server(&h, io, api.session()))
+            .map_err(|e| warn!("Handshake failed: {}", e)));
+
+        Ok(())
+    }).chain_err(|| "Unix socket listener failed")))
+}
+
+// A socket file left behind by a previous agent will stop us binding, so
+// clean it up. Anything that isn't a socket is left well alone.
+fn remove_stale_socket(path: &Path) -> Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(meta) => if meta.file_type().is_socket() {
+            fs::remove_file(path).chain_err(|| "Could not remove stale socket")
+        } else {
+            bail!("Socket path {} exists and is not a socket", path.display())
+        },
+        Err(_) => Ok(()),
+    }
+}
+
+fn tls_acceptor(config: &TlsConfig) -> Result<SslAcceptor> {
+    let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())
+        .chain_err(|| "Could not create TLS acceptor")?;
+    builder.set_certificate_chain_file(&config.cert)
+        .chain_err(|| "Could not load TLS certificate")?;
+    builder.set_private_key_file(&config.key, SslFiletype::PEM)
+        .chain_err(|| "Could not load TLS private key")?;
+    builder.check_private_key()
+        .chain_err(|| "TLS private key does not match certificate")?;
+
+    if let Some(ref ca) = config.ca {
+        builder.set_ca_file(ca).chain_err(|| "Could not load CA certificate")?;
+        builder.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+    }
+
+    Ok(builder.build())
+}
+
+fn denied_to_msg(reason: String) -> WireMessage {
+    let value = serde_json::to_value(ResponseResult::Denied(reason))
+        .expect("Cannot serialize ResponseResult::Denied. This is bad...");
+    Message::WithoutBody(value)
+}
+
+fn error_to_msg<E: Into<serializable::Error>>(e: E) -> WireMessage {
+    let response = ResponseResult::Err(e.into());
     // If we can't serialize this, we can't serialize anything, so
     // panicking is appropriate.
     let value = serde_json::to_value(response)
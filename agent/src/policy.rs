@@ -0,0 +1,383 @@
+// Copyright 2015-2017 Intecture Developers.
+//
+// Licensed under the Mozilla Public License 2.0 <LICENSE or
+// https://www.tldrlegal.com/l/mpl-2.0>. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Authorization policy for incoming requests.
+//!
+//! A policy is an ordered list of rules. Each request is checked against the
+//! rules in turn and the first matching rule decides whether it's allowed.
+//! If no rule matches, the policy's default action applies.
+//!
+//! ```toml
+//! [policy]
+//! default = "deny"
+//!
+//! [[policy.rule]]
+//! request = "TelemetryLoad"
+//! action = "allow"
+//!
+//! [[policy.rule]]
+//! request = "Service*"
+//! subjects = ["nginx", "php-fpm"]
+//! action = "allow"
+//! ```
+
+use intecture_api::command::ExecOptions;
+use intecture_api::remote::Request;
+use std::fmt;
+use std::path::{Component, Path};
+
+/// Shells that run their `-c` argument as a command line. Whatever the
+/// client claims, commands run by one of these are treated as shell
+/// commands.
+const SHELLS: [&str; 10] = ["ash", "bash", "csh", "dash", "fish", "ksh", "mksh", "sh", "tcsh", "zsh"];
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    #[default]
+    Allow,
+    Deny,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Policy {
+    /// Action to take when no rule matches
+    #[serde(default)]
+    default: Action,
+    #[serde(default, rename = "rule")]
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Rule {
+    /// Request name to match, e.g. "PackageInstall". A trailing `*` matches
+    /// any request starting with the given prefix.
+    request: String,
+    /// Request subjects to match, e.g. package or service names, or the
+    /// command line for `CommandExec`. Subjects follow the same wildcard
+    /// rules as `request`, except for commands:
+    ///
+    /// - A command run without a shell is split on whitespace and matched
+    ///   against the command's arguments one by one, so `systemctl status *`
+    ///   matches `["systemctl", "status", "nginx"]`, but not
+    ///   `["systemctl", "status", "nginx", "--user"]`.
+    /// - A command run by a shell must look like `<shell> -c <cmd>`, where
+    ///   `<shell>` is one of `SHELLS`, and `<cmd>` must match exactly.
+    ///
+    /// If omitted, any subject matches.
+    subjects: Option<Vec<String>>,
+    /// Users that an allowed `CommandExec` may run as. Commands run as the
+    /// agent's own user unless this allows otherwise.
+    #[serde(default)]
+    users: Vec<String>,
+    /// Groups that an allowed `CommandExec` may run as.
+    #[serde(default)]
+    groups: Vec<String>,
+    /// Whether an allowed `CommandExec` may use `sudo` or `doas`.
+    #[serde(default)]
+    escalate: bool,
+    /// Names of environment variables that an allowed `CommandExec` may
+    /// set, e.g. "LC_*". Variables like `LD_PRELOAD` can change what a
+    /// command does, so none are allowed by default.
+    #[serde(default)]
+    env: Vec<String>,
+    /// Working directories that an allowed `CommandExec` may use.
+    #[serde(default)]
+    cwd: Vec<String>,
+    action: Action,
+}
+
+impl Policy {
+    /// Check whether a request is allowed, returning the reason if it isn't.
+    ///
+    /// Requests with several subjects, e.g. `PackageInstallMany`, are only
+    /// allowed if each subject would be allowed on its own.
+    pub fn check(&self, request: &Request) -> Result<(), String> {
+        if let Request::CommandExec(_, ref cmd, ref options) = *request {
+            return self.check_subject(request, Some(&exec_subject(cmd, options.shell)));
+        }
+
+        let subjects = request.subjects();
+        if subjects.is_empty() {
+            return self.check_subject(request, None);
+        }
+
+        for subject in &subjects {
+            self.check_subject(request, Some(&Subject::Name(subject)))?;
+        }
+        Ok(())
+    }
+
+    fn check_subject(&self, request: &Request, subject: Option<&Subject>) -> Result<(), String> {
+        let action = self.rules.iter()
+            .find(|r| r.matches(request, subject))
+            .map(|r| r.action)
+            .unwrap_or(self.default);
+
+        match action {
+            Action::Allow => Ok(()),
+            Action::Deny => Err(match subject {
+                Some(s) => format!("{} is not permitted for \"{}\"", request.name(), s),
+                None => format!("{} is not permitted", request.name()),
+            }),
+        }
+    }
+}
+
+impl Rule {
+    fn matches(&self, request: &Request, subject: Option<&Subject>) -> bool {
+        if !glob_match(&self.request, request.name()) {
+            return false;
+        }
+
+        // Rules that deny a command do so however it's run, but rules that
+        // allow one only allow the options they mention.
+        if let Request::CommandExec(_, _, ref options) = *request {
+            if self.action == Action::Allow && !self.allows_options(options) {
+                return false;
+            }
+        }
+
+        match self.subjects {
+            Some(ref subjects) => match subject {
+                Some(&Subject::Name(name)) => subjects.iter().any(|s| glob_match(s, name)),
+                Some(&Subject::Argv(argv)) => subjects.iter().any(|s| argv_match(s, argv)),
+                // A shell will happily run whatever follows a matching
+                // prefix, e.g. `systemctl status; rm -rf /`, so wildcards
+                // are no good for shell commands.
+                Some(&Subject::Shell(cmd)) => subjects.iter().any(|s| s == cmd),
+                Some(&Subject::Invalid(_)) | None => false,
+            },
+            None => true,
+        }
+    }
+
+    fn allows_options(&self, options: &ExecOptions) -> bool {
+        let allows = |patterns: &[String], value: &Option<String>| match *value {
+            Some(ref v) => patterns.iter().any(|p| glob_match(p, v)),
+            None => true,
+        };
+
+        let cwd = match options.cwd {
+            // Don't let `/srv/*` match `/srv/../etc`
+            Some(ref cwd) => !Path::new(cwd).components().any(|c| c == Component::ParentDir)
+                && self.cwd.iter().any(|p| glob_match(p, cwd)),
+            None => true,
+        };
+
+        cwd && allows(&self.users, &options.user)
+            && allows(&self.groups, &options.group)
+            && (self.escalate || options.escalate.is_none())
+            && options.env.iter().all(|(k, _)| self.env.iter().any(|p| glob_match(p, k)))
+    }
+}
+
+/// What a request acts upon, as far as rules are concerned.
+enum Subject<'a> {
+    Name(&'a str),
+    /// A command run without a shell
+    Argv(&'a [String]),
+    /// The command line given to a shell
+    Shell(&'a str),
+    /// A shell command that isn't `<shell> -c <cmd>`, which no subject
+    /// matches
+    Invalid(&'a [String]),
+}
+
+impl<'a> fmt::Display for Subject<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Subject::Name(s) | Subject::Shell(s) => write!(f, "{}", s),
+            Subject::Argv(argv) | Subject::Invalid(argv) => write!(f, "{}", argv.join(" ")),
+        }
+    }
+}
+
+// We don't trust `ExecOptions::shell`, as it's up to the client. Anything
+// run by a shell gets the stricter shell rules, and has to be in the form
+// we expect, so that there's exactly one command line to check.
+fn exec_subject(cmd: &[String], shell: bool) -> Subject<'_> {
+    if !shell && !cmd.first().is_some_and(|c| is_shell(c)) {
+        return Subject::Argv(cmd);
+    }
+
+    if cmd.len() == 3 && is_shell(&cmd[0]) && cmd[1] == "-c" {
+        Subject::Shell(&cmd[2])
+    } else {
+        Subject::Invalid(cmd)
+    }
+}
+
+fn is_shell(program: &str) -> bool {
+    Path::new(program).file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| SHELLS.contains(&name))
+}
+
+// Match each argument against its own pattern, so that a wildcard can't
+// swallow extra arguments.
+fn argv_match(pattern: &str, argv: &[String]) -> bool {
+    let patterns: Vec<&str> = pattern.split_whitespace().collect();
+    patterns.len() == argv.len() && patterns.iter().zip(argv).all(|(p, a)| glob_match(p, a))
+}
+
+fn glob_match(pattern: &str, value: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => value.starts_with(prefix),
+        None => pattern == value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use intecture_api::command::{Escalation, ExecOptions};
+    use intecture_api::remote::Request;
+    use super::*;
+    use toml;
+
+    fn exec(cmd: &[&str], shell: bool) -> Request {
+        let options = ExecOptions { shell: shell, ..ExecOptions::default() };
+        Request::CommandExec(None, cmd.iter().map(|c| c.to_string()).collect(), options)
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("PackageInstall", "PackageInstall"));
+        assert!(!glob_match("PackageInstall", "PackageInstallMany"));
+        assert!(glob_match("Package*", "PackageInstall"));
+        assert!(glob_match("Package*", "Package"));
+        assert!(!glob_match("Package*", "Service"));
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("", "anything"));
+    }
+
+    #[test]
+    fn test_argv_match() {
+        let argv = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert!(argv_match("systemctl status *", &argv(&["systemctl", "status", "nginx"])));
+        assert!(argv_match("systemctl  status\tnginx", &argv(&["systemctl", "status", "nginx"])));
+        assert!(!argv_match("systemctl status *", &argv(&["systemctl", "status", "nginx", "--user"])));
+        assert!(!argv_match("systemctl status *", &argv(&["systemctl", "status"])));
+        assert!(!argv_match("systemctl status", &argv(&["systemctl status"])));
+    }
+
+    #[test]
+    fn test_check() {
+        let policy: Policy = toml::from_str(r#"
+            default = "deny"
+
+            [[rule]]
+            request = "PackageInstall*"
+            subjects = ["nginx", "php*"]
+            action = "allow"
+
+            [[rule]]
+            request = "CommandExec"
+            subjects = ["echo hi*"]
+            action = "allow"
+        "#).unwrap();
+
+        assert!(policy.check(&Request::PackageInstall(None, "nginx".into(), None)).is_ok());
+        assert!(policy.check(&Request::PackageInstall(None, "php-fpm".into(), None)).is_ok());
+        assert!(policy.check(&Request::PackageInstall(None, "apache2".into(), None)).is_err());
+        assert!(policy.check(&Request::PackageInstallMany(None, vec!["nginx".into(), "php-fpm".into()])).is_ok());
+        assert!(policy.check(&Request::PackageInstallMany(None, vec!["nginx".into(), "apache2".into()])).is_err());
+        assert!(policy.check(&Request::TelemetryLoad).is_err());
+
+        // Wildcards match commands without a shell, but not with one
+        assert!(policy.check(&exec(&["echo", "hi"], false)).is_ok());
+        assert!(policy.check(&exec(&["echo", "hi there"], false)).is_ok());
+        assert!(policy.check(&exec(&["echo", "hi", "there"], false)).is_err());
+        assert!(policy.check(&exec(&["echo"], false)).is_err());
+        assert!(policy.check(&exec(&["/bin/sh", "-c", "echo hi; id"], true)).is_err());
+        assert!(policy.check(&exec(&["/bin/sh", "-c", "echo hi*"], true)).is_ok());
+    }
+
+    #[test]
+    fn test_check_options() {
+        let policy: Policy = toml::from_str(r#"
+            default = "deny"
+
+            [[rule]]
+            request = "CommandExec"
+            subjects = ["whoami"]
+            users = ["postgres"]
+            escalate = true
+            action = "allow"
+
+            [[rule]]
+            request = "CommandExec"
+            subjects = ["make"]
+            env = ["LC_*"]
+            cwd = ["/srv/*"]
+            action = "allow"
+
+            [[rule]]
+            request = "CommandExec"
+            subjects = ["id"]
+            action = "allow"
+        "#).unwrap();
+
+        let with = |cmd: &str, f: &dyn Fn(&mut ExecOptions)| {
+            let mut options = ExecOptions::default();
+            f(&mut options);
+            policy.check(&Request::CommandExec(None, vec![cmd.into()], options))
+        };
+
+        assert!(with("id", &|_| ()).is_ok());
+        assert!(with("id", &|o| o.user = Some("root".into())).is_err());
+        assert!(with("id", &|o| o.escalate = Some(Escalation::Sudo)).is_err());
+        assert!(with("id", &|o| o.env.push(("LD_PRELOAD".into(), "/tmp/evil.so".into()))).is_err());
+        assert!(with("id", &|o| o.cwd = Some("/tmp".into())).is_err());
+
+        assert!(with("whoami", &|o| o.user = Some("postgres".into())).is_ok());
+        assert!(with("whoami", &|o| { o.user = Some("postgres".into()); o.escalate = Some(Escalation::Doas); }).is_ok());
+        assert!(with("whoami", &|o| o.user = Some("root".into())).is_err());
+        assert!(with("whoami", &|o| o.group = Some("wheel".into())).is_err());
+
+        assert!(with("make", &|o| o.env.push(("LC_ALL".into(), "C".into()))).is_ok());
+        assert!(with("make", &|o| o.env.push(("LD_PRELOAD".into(), "/tmp/evil.so".into()))).is_err());
+        assert!(with("make", &|o| o.cwd = Some("/srv/app".into())).is_ok());
+        assert!(with("make", &|o| o.cwd = Some("/srv/../etc".into())).is_err());
+
+        // Deny rules don't care how a command is run
+        let policy: Policy = toml::from_str(r#"
+            [[rule]]
+            request = "CommandExec"
+            subjects = ["reboot"]
+            action = "deny"
+        "#).unwrap();
+        let options = ExecOptions { escalate: Some(Escalation::Sudo), ..ExecOptions::default() };
+        assert!(policy.check(&Request::CommandExec(None, vec!["reboot".into()], options)).is_err());
+    }
+
+    #[test]
+    fn test_check_shell() {
+        let policy: Policy = toml::from_str(r#"
+            default = "deny"
+
+            [[rule]]
+            request = "CommandExec"
+            subjects = ["echo hi"]
+            action = "allow"
+        "#).unwrap();
+
+        assert!(policy.check(&exec(&["/bin/sh", "-c", "echo hi"], true)).is_ok());
+        assert!(policy.check(&exec(&["/usr/bin/bash", "-c", "echo hi"], true)).is_ok());
+
+        // Only the command line given to `-c` is matched, so extra
+        // arguments can't smuggle in another command
+        assert!(policy.check(&exec(&["/bin/sh", "-c", "id; #", "echo hi"], true)).is_err());
+        assert!(policy.check(&exec(&["/bin/sh", "echo hi"], true)).is_err());
+        assert!(policy.check(&exec(&["/usr/bin/python", "-c", "echo hi"], true)).is_err());
+
+        // Shells are shells, whatever the client says
+        assert!(policy.check(&exec(&["/bin/sh", "-c", "echo hi; id"], false)).is_err());
+        assert!(policy.check(&exec(&["/bin/sh", "-c", "echo hi"], false)).is_ok());
+        assert!(policy.check(&exec(&["sh", "-c", "echo hi", "extra"], false)).is_err());
+    }
+}
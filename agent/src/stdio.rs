@@ -0,0 +1,67 @@
+// Copyright 2015-2017 Intecture Developers.
+//
+// Licensed under the Mozilla Public License 2.0 <LICENSE or
+// https://www.tldrlegal.com/l/mpl-2.0>. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! A single session transport over the agent's stdin and stdout.
+
+use futures::Poll;
+use futures::sync::oneshot;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::io::FromRawFd;
+use tokio_core::reactor::{Handle, PollEvented};
+use tokio_file_unix;
+use tokio_io::{AsyncRead, AsyncWrite};
+
+type Fd = PollEvented<tokio_file_unix::File<File>>;
+
+/// Duplex stream that reads from stdin and writes to stdout.
+///
+/// The transport is dropped when the client hangs up, which resolves the
+/// `oneshot::Receiver` returned by `Stdio::new()`.
+pub struct Stdio {
+    stdin: Fd,
+    stdout: Fd,
+    _done: oneshot::Sender<()>,
+}
+
+impl Stdio {
+    pub fn new(handle: &Handle) -> io::Result<(Stdio, oneshot::Receiver<()>)> {
+        // We take ownership of the process' stdio here, so nobody else
+        // had better be writing to stdout!
+        let (stdin, stdout) = unsafe { (File::from_raw_fd(0), File::from_raw_fd(1)) };
+        let (tx, rx) = oneshot::channel();
+
+        Ok((Stdio {
+            stdin: tokio_file_unix::File::new_nb(stdin)?.into_io(handle)?,
+            stdout: tokio_file_unix::File::new_nb(stdout)?.into_io(handle)?,
+            _done: tx,
+        }, rx))
+    }
+}
+
+impl Read for Stdio {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stdin.read(buf)
+    }
+}
+
+impl Write for Stdio {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stdout.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stdout.flush()
+    }
+}
+
+impl AsyncRead for Stdio {}
+
+impl AsyncWrite for Stdio {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.stdout.shutdown()
+    }
+}
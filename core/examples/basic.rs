@@ -26,15 +26,20 @@ fn main() {
 
         // Let's start with something basic - a shell command.
         let cmd = Command::new(&host, "whoami", None);
-        cmd.exec().and_then(|mut status| {
+        cmd.exec().and_then(|status| {
+            // Commands can be made idempotent with guards like `creates()`,
+            // in which case `exec()` returns `None` if there's nothing to
+            // do. We haven't set any, so our command always runs.
+            let mut status = status.unwrap();
+
             // At this point, our command is running. As the API is
             // asynchronous, we don't have to wait for it to finish before
             // inspecting its output. This is called "streaming".
 
-            // First let's grab the stream from `CommandStatus`. This stream is
-            // a stream of strings, each of which represents a line of command
-            // output. We can use the `for_each` combinator to print these
-            // lines to stdout.
+            // First let's grab the stream from `CommandStatus`. This stream
+            // yields each line of command output, tagged with whether it came
+            // from stdout or stderr. We can use the `for_each` combinator to
+            // print these lines to stdout.
             //
             // If printing isn't your thing, you are also free to lick them or
             // whatever you're into. I'm not here to judge.
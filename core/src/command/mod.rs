@@ -7,20 +7,31 @@
 //! Endpoint for running shell commands.
 //!
 //! A shell command is represented by the `Command` struct, which is not
-//! idempotent.
+//! idempotent unless you give it guards, e.g. `Command::creates()`.
 
 mod providers;
 
+use error_chain::ChainedError;
 use errors::*;
-use futures::{future, Future, Poll};
+use errors::serializable;
+use futures::{future, stream, Async, Future, Poll, Sink};
 use futures::stream::Stream;
 use futures::sync::oneshot;
+use futures::task::{self, Task};
 use host::Host;
+use libc;
 use remote::{Request, Response};
-use std::io;
-#[doc(hidden)] pub use self::providers::{factory, Generic};
+use std::{fmt, io, result};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Duration;
+#[doc(hidden)] pub use self::providers::{factory, CommandProvider, Generic, Processes};
 pub use self::providers::Provider;
 use serde_json;
+use tempfile::{Builder, NamedTempFile};
 use tokio_proto::streaming::{Body, Message};
 
 #[cfg(not(windows))]
@@ -28,6 +39,14 @@ const DEFAULT_SHELL: [&'static str; 2] = ["/bin/sh", "-c"];
 #[cfg(windows)]
 const DEFAULT_SHELL: [&'static str; 1] = ["yeah...we don't currently support windows :("];
 
+const CHUNK_STDOUT: u8 = 0;
+const CHUNK_STDERR: u8 = 1;
+const CHUNK_EXIT_STATUS: u8 = 2;
+const CHUNK_ERROR: u8 = 3;
+
+// Size of the body chunks that stdin is sent in
+const STDIN_CHUNK_SIZE: usize = 64 * 1024;
+
 /// Represents a shell command to be executed on a host.
 ///
 ///## Examples
@@ -50,7 +69,9 @@ const DEFAULT_SHELL: [&'static str; 1] = ["yeah...we don't currently support win
 ///let host = Local::new(&handle).wait().unwrap();
 ///
 ///let cmd = Command::new(&host, "ls /path/to/foo", None);
-///let result = cmd.exec().and_then(|mut status| {
+///let result = cmd.exec().and_then(|status| {
+///    // There are no guards, so the command always runs
+///    let mut status = status.unwrap();
 ///    // Print the command's stdout/stderr to stdout
 ///    status.take_stream().unwrap()
 ///        .for_each(|line| { println!("{}", line); Ok(()) })
@@ -72,7 +93,8 @@ const DEFAULT_SHELL: [&'static str; 1] = ["yeah...we don't currently support win
 ///
 /// We can also save all output to a string for later use. **Be careful** doing
 /// this as you could run out of memory on your heap if the output buffer is
-/// too big.
+/// too big. Use `CommandStatus::result_with()` to limit how much output is
+/// kept.
 ///
 ///```no_run
 ///extern crate futures;
@@ -92,16 +114,18 @@ const DEFAULT_SHELL: [&'static str; 1] = ["yeah...we don't currently support win
 ///
 ///let cmd = Command::new(&host, "ls /path/to/foo", None);
 ///let result = cmd.exec().and_then(|status| {
+///    let status = status.unwrap();
 ///    status.result().unwrap()
 ///        .map(|_output| {
 ///            // Our command finished successfully. Now we can do something
-///            // with our output here.
+///            // with our output here, which is split into `stdout` and
+///            // `stderr`.
 ///        })
 ///        .map_err(|e| {
 ///            // Our command errored out. Let's grab the output and see what
 ///            // went wrong.
 ///            match *e.kind() {
-///                ErrorKind::Command(ref output) => println!("Oh noes! {}", output),
+///                ErrorKind::Command { ref stderr, .. } => println!("Oh noes! {}", stderr),
 ///                _ => unreachable!(),
 ///            }
 ///            e
@@ -131,7 +155,9 @@ const DEFAULT_SHELL: [&'static str; 1] = ["yeah...we don't currently support win
 ///let host = Local::new(&handle).wait().unwrap();
 ///
 ///let cmd = Command::new(&host, "ls /path/to/foo", None);
-///let result = cmd.exec().and_then(|mut status| {
+///let result = cmd.exec().and_then(|status| {
+///    // There are no guards, so the command always runs
+///    let mut status = status.unwrap();
 ///    status.map(|exit_status| {
 ///        if exit_status.success {
 ///            println!("Huzzah!");
@@ -144,24 +170,163 @@ const DEFAULT_SHELL: [&'static str; 1] = ["yeah...we don't currently support win
 ///core.run(result).unwrap();
 ///# }
 ///```
+#[derive(Clone)]
 pub struct Command<H: Host> {
     host: H,
     provider: Option<Provider>,
     cmd: Vec<String>,
+    options: ExecOptions,
+    stdin: Option<Vec<u8>>,
+    guards: Vec<Guard>,
+}
+
+// Checks that decide whether a `Command` needs to run
+#[derive(Clone, Debug)]
+enum Guard {
+    Creates(String),
+    Removes(String),
+    Unless(String),
+    OnlyIf(String),
+}
+
+/// Options for the environment that a `Command` runs in.
+///
+/// You don't normally need to create this yourself. Use the builder methods
+/// on `Command` instead, e.g. `Command::env()`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ExecOptions {
+    /// The last argument is a command line for a shell, rather than the
+    /// command being a plain argv.
+    pub shell: bool,
+    /// Environment variables to set, in the order they were added.
+    pub env: Vec<(String, String)>,
+    /// Clear the inherited environment before setting `env`.
+    pub env_clear: bool,
+    /// Working directory for the command.
+    pub cwd: Option<String>,
+    /// File mode creation mask for the command, e.g. `0o022`.
+    pub umask: Option<u32>,
+    /// User to run the command as.
+    pub user: Option<String>,
+    /// Group to run the command as. Defaults to `user`'s primary group.
+    pub group: Option<String>,
+    /// How to gain privileges if we aren't root.
+    pub escalate: Option<Escalation>,
+    /// How long the command may run before it is terminated.
+    pub timeout: Option<Duration>,
+    /// Run the command in a PTY with this window size.
+    pub pty: Option<WindowSize>,
+}
+
+/// The size of a PTY's window, in characters.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct WindowSize {
+    pub rows: u16,
+    pub cols: u16,
+}
+
+/// Tools that a `Command` can use to gain privileges when the host isn't
+/// running as root.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Escalation {
+    Sudo,
+    Doas,
 }
 
 /// Represents the status of a running `Command`, including the output stream
 /// and exit status.
 pub struct CommandStatus {
-    stream: Option<Box<Stream<Item = String, Error = Error>>>,
+    stream: Option<Box<Stream<Item = Output, Error = Error>>>,
+    split: Option<Rc<RefCell<Split>>>,
     exit_status: Option<Box<Future<Item = ExitStatus, Error = Error>>>,
+    pid: Option<u32>,
+    // The most output that `result()` keeps, if it's limited
+    limit: Option<usize>,
+    // Sends requests that control the running command, e.g. `CommandCancel`
+    control: Box<Fn(Request, &'static str) -> Box<Future<Item = (), Error = Error>>>,
 }
 
 /// Represents the exit status of a `Command` as a `Result`-like `Future`. If
 /// the command succeeded, the command output is returned. If it failed, an
 /// error containing the command's output is returned.
 pub struct CommandResult {
-    inner: Box<Future<Item = String, Error = Error>>,
+    inner: Box<Future<Item = CommandOutput, Error = Error>>,
+}
+
+/// A line of command output, tagged with the stream it came from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Output {
+    Stdout(String),
+    Stderr(String),
+}
+
+/// The output of a finished command.
+#[derive(Clone, Debug, Default)]
+pub struct CommandOutput {
+    pub stdout: String,
+    pub stderr: String,
+    /// Whether some of the output was dropped, e.g. by `Capture::Tail`.
+    pub truncated: bool,
+    /// The file holding stdout, if captured with `Capture::TempFile`.
+    pub stdout_file: Option<PathBuf>,
+    /// The file holding stderr, if captured with `Capture::TempFile`.
+    pub stderr_file: Option<PathBuf>,
+}
+
+/// How `CommandStatus::result_with()` captures a command's output.
+pub enum Capture {
+    /// Keep all output in memory. This is what `CommandStatus::result()`
+    /// does.
+    All,
+    /// Keep the first _n_ bytes of stdout and stderr each, dropping the
+    /// rest.
+    Head(usize),
+    /// Keep the last _n_ bytes of stdout and stderr each, dropping the
+    /// rest.
+    Tail(usize),
+    /// Write stdout and stderr to temporary files as they arrive, rather
+    /// than keeping them in memory. The files hold the same output that
+    /// `Capture::All` would have kept. The paths are returned in
+    /// `CommandOutput` (or `ErrorKind::Command` if the command failed),
+    /// and it's up to you to delete the files when you're done with them.
+    TempFile,
+    /// Pass each line to a callback as it arrives, rather than keeping it.
+    Lines(Box<FnMut(Output)>),
+}
+
+// Collects command output as specified by a `Capture`
+struct Captured {
+    capture: Capture,
+    output: CommandOutput,
+    files: Option<(BufWriter<NamedTempFile>, BufWriter<NamedTempFile>)>,
+}
+
+// Shares a command's output between its stdout and stderr streams. Whichever
+// stream is polled reads from the output and buffers lines for the other.
+struct Split {
+    stream: Box<Stream<Item = Output, Error = Error>>,
+    done: bool,
+    stdout: Option<VecDeque<String>>,
+    stderr: Option<VecDeque<String>>,
+    stdout_task: Option<Task>,
+    stderr_task: Option<Task>,
+}
+
+// Either the stdout or the stderr half of a `Split`.
+struct SplitStream {
+    split: Rc<RefCell<Split>>,
+    stdout: bool,
+}
+
+/// A chunk of a command's body stream. Each chunk starts with a byte that
+/// identifies its type, so the command's output can never be mistaken for
+/// its exit status.
+#[doc(hidden)]
+pub enum Chunk {
+    Stdout(Vec<u8>),
+    Stderr(Vec<u8>),
+    ExitStatus(ExitStatus),
+    Error(serializable::Error),
 }
 
 /// The status of a finished command.
@@ -178,6 +343,31 @@ pub struct ExitStatus {
     /// On Unix, this will return `None` if the process was terminated by a
     /// signal.
     pub code: Option<i32>,
+    /// Returns the signal that terminated the process, if any.
+    pub signal: Option<Signal>,
+}
+
+/// A Unix signal that can be sent to a running command.
+///
+/// Signal numbers differ between platforms, so signals are sent by name and
+/// translated on the host. Use `Other` for signals that aren't listed here,
+/// bearing in mind that its number is _not_ translated.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Signal {
+    Hup,
+    Int,
+    Quit,
+    Kill,
+    Usr1,
+    Usr2,
+    Pipe,
+    Alrm,
+    Term,
+    Cont,
+    Stop,
+    Tstp,
+    Winch,
+    Other(i32),
 }
 
 impl<H: Host + 'static> Command<H> {
@@ -198,6 +388,51 @@ impl<H: Host + 'static> Command<H> {
             host: host.clone(),
             provider: None,
             cmd: args,
+            options: ExecOptions {
+                shell: true,
+                ..ExecOptions::default()
+            },
+            stdin: None,
+            guards: Vec::new(),
+        }
+    }
+
+    /// Create a new `Command` that runs `argv` directly, without a shell.
+    ///
+    /// The first item is the program to run and the rest are its arguments,
+    /// which are passed through untouched. There's no quoting to get wrong,
+    /// so this is the safe way to run commands that include untrusted data,
+    /// like package names or file paths. Of course, this also means there
+    /// are no pipes, redirects or variable expansion.
+    ///
+    ///## Example
+    ///```
+    ///extern crate futures;
+    ///extern crate intecture_api;
+    ///extern crate tokio_core;
+    ///
+    ///use futures::Future;
+    ///use intecture_api::prelude::*;
+    ///use tokio_core::reactor::Core;
+    ///
+    ///# fn main() {
+    ///let mut core = Core::new().unwrap();
+    ///let handle = core.handle();
+    ///
+    ///let host = Local::new(&handle).wait().unwrap();
+    ///
+    ///// The file name is never seen by a shell, so this is harmless
+    ///let cmd = Command::from_argv(&host, &["ls", "-l", "foo; rm -rf /"]);
+    ///# }
+    ///```
+    pub fn from_argv(host: &H, argv: &[&str]) -> Command<H> {
+        Command {
+            host: host.clone(),
+            provider: None,
+            cmd: argv.iter().map(|a| (*a).to_owned()).collect(),
+            options: ExecOptions::default(),
+            stdin: None,
+            guards: Vec::new(),
         }
     }
 
@@ -228,15 +463,247 @@ impl<H: Host + 'static> Command<H> {
         cmd
     }
 
+    /// Set an environment variable for the command.
+    ///
+    ///## Example
+    ///```
+    ///extern crate futures;
+    ///extern crate intecture_api;
+    ///extern crate tokio_core;
+    ///
+    ///use futures::Future;
+    ///use intecture_api::prelude::*;
+    ///use tokio_core::reactor::Core;
+    ///
+    ///# fn main() {
+    ///let mut core = Core::new().unwrap();
+    ///let handle = core.handle();
+    ///
+    ///let host = Local::new(&handle).wait().unwrap();
+    ///
+    ///let cmd = Command::new(&host, "make install", None)
+    ///    .env("PREFIX", "/usr/local")
+    ///    .cwd("/path/to/src")
+    ///    .umask(0o022);
+    ///# }
+    pub fn env(mut self, key: &str, value: &str) -> Command<H> {
+        self.options.env.push((key.into(), value.into()));
+        self
+    }
+
+    /// Clear the environment that the command would otherwise inherit from
+    /// the host. Variables set with `env()` are still set.
+    pub fn env_clear(mut self) -> Command<H> {
+        self.options.env_clear = true;
+        self
+    }
+
+    /// Set the working directory for the command.
+    pub fn cwd(mut self, dir: &str) -> Command<H> {
+        self.options.cwd = Some(dir.into());
+        self
+    }
+
+    /// Set the file mode creation mask for the command.
+    pub fn umask(mut self, mask: u32) -> Command<H> {
+        self.options.umask = Some(mask);
+        self
+    }
+
+    /// Run the command as `user`.
+    ///
+    /// If the host is running as root, it switches to the user itself.
+    /// Otherwise you'll need to enable privilege escalation with
+    /// `escalate()`.
+    ///
+    ///## Example
+    ///```
+    ///extern crate futures;
+    ///extern crate intecture_api;
+    ///extern crate tokio_core;
+    ///
+    ///use futures::Future;
+    ///use intecture_api::command::Escalation;
+    ///use intecture_api::prelude::*;
+    ///use tokio_core::reactor::Core;
+    ///
+    ///# fn main() {
+    ///let mut core = Core::new().unwrap();
+    ///let handle = core.handle();
+    ///
+    ///let host = Local::new(&handle).wait().unwrap();
+    ///
+    ///let cmd = Command::new(&host, "bundle exec rake db:migrate", None)
+    ///    .user("app")
+    ///    .escalate(Escalation::Sudo);
+    ///# }
+    pub fn user(mut self, user: &str) -> Command<H> {
+        self.options.user = Some(user.into());
+        self
+    }
+
+    /// Run the command as `group`, rather than the primary group of the user
+    /// it runs as.
+    pub fn group(mut self, group: &str) -> Command<H> {
+        self.options.group = Some(group.into());
+        self
+    }
+
+    /// Use `tool` to gain privileges if the host isn't running as root. This
+    /// is ignored if the host is root.
+    ///
+    /// If no user has been set with `user()`, the command runs as root.
+    /// Note that sudo and doas may reset the environment, depending on how
+    /// they're configured. Both are run non-interactively, so they must be
+    /// configured not to ask for a password.
+    pub fn escalate(mut self, tool: Escalation) -> Command<H> {
+        self.options.escalate = Some(tool);
+        self
+    }
+
+    /// Terminate the command if it runs for longer than `timeout`.
+    ///
+    /// The command is first sent SIGTERM, then SIGKILL if it still hasn't
+    /// exited after a grace period. Its output stream and `ExitStatus` then
+    /// resolve to an `ErrorKind::CommandTimedOut` error.
+    ///
+    ///## Example
+    ///```
+    ///extern crate futures;
+    ///extern crate intecture_api;
+    ///extern crate tokio_core;
+    ///
+    ///use futures::Future;
+    ///use intecture_api::prelude::*;
+    ///use std::time::Duration;
+    ///use tokio_core::reactor::Core;
+    ///
+    ///# fn main() {
+    ///let mut core = Core::new().unwrap();
+    ///let handle = core.handle();
+    ///
+    ///let host = Local::new(&handle).wait().unwrap();
+    ///
+    ///let cmd = Command::new(&host, "apt-get -y upgrade", None)
+    ///    .timeout(Duration::from_secs(600));
+    ///# }
+    pub fn timeout(mut self, timeout: Duration) -> Command<H> {
+        self.options.timeout = Some(timeout);
+        self
+    }
+
+    /// Run the command in a pseudo-terminal (PTY) with the given window
+    /// size, for programs that behave differently without a TTY.
+    ///
+    /// The PTY takes the place of stdin, stdout and stderr, so all output is
+    /// sent as stdout. Output is sent exactly as the command wrote it,
+    /// including any escape codes and carriage returns, rather than line by
+    /// line. Use `CommandStatus::resize()` to change the window size while
+    /// the command runs.
+    pub fn pty(mut self, rows: u16, cols: u16) -> Command<H> {
+        self.options.pty = Some(WindowSize { rows: rows, cols: cols });
+        self
+    }
+
+    /// Send `input` to the command's stdin. Once all of the input has been
+    /// written, stdin is closed.
+    pub fn stdin<B: Into<Vec<u8>>>(mut self, input: B) -> Command<H> {
+        self.stdin = Some(input.into());
+        self
+    }
+
+    /// Only run the command if `path` doesn't exist, e.g. the file that the
+    /// command creates.
+    ///
+    /// Guards are checked on the host before the command runs, with the
+    /// same working directory, environment and user as the command itself.
+    /// If any guard says the command isn't needed, it won't run and
+    /// `exec()` will return `None`. Guards are checked in the order they
+    /// were added.
+    ///
+    ///## Example
+    ///```
+    ///extern crate futures;
+    ///extern crate intecture_api;
+    ///extern crate tokio_core;
+    ///
+    ///use futures::Future;
+    ///use intecture_api::prelude::*;
+    ///use tokio_core::reactor::Core;
+    ///
+    ///# fn main() {
+    ///let mut core = Core::new().unwrap();
+    ///let handle = core.handle();
+    ///
+    ///let host = Local::new(&handle).wait().unwrap();
+    ///
+    ///let cmd = Command::new(&host, "tar xzf app.tar.gz", None)
+    ///    .cwd("/opt")
+    ///    .creates("app/bin/app");
+    ///# }
+    pub fn creates(mut self, path: &str) -> Command<H> {
+        self.guards.push(Guard::Creates(path.into()));
+        self
+    }
+
+    /// Only run the command if `path` exists, e.g. the file that the command
+    /// removes. See `creates()` for details on how guards are checked.
+    pub fn removes(mut self, path: &str) -> Command<H> {
+        self.guards.push(Guard::Removes(path.into()));
+        self
+    }
+
+    /// Only run the command if the shell command `guard` fails. See
+    /// `creates()` for details on how guards are checked.
+    ///
+    ///## Example
+    ///```
+    ///extern crate futures;
+    ///extern crate intecture_api;
+    ///extern crate tokio_core;
+    ///
+    ///use futures::Future;
+    ///use intecture_api::prelude::*;
+    ///use tokio_core::reactor::Core;
+    ///
+    ///# fn main() {
+    ///let mut core = Core::new().unwrap();
+    ///let handle = core.handle();
+    ///
+    ///let host = Local::new(&handle).wait().unwrap();
+    ///
+    ///let cmd = Command::new(&host, "useradd app", None)
+    ///    .unless("id app");
+    ///# }
+    pub fn unless(mut self, guard: &str) -> Command<H> {
+        self.guards.push(Guard::Unless(guard.into()));
+        self
+    }
+
+    /// Only run the command if the shell command `guard` succeeds. See
+    /// `creates()` for details on how guards are checked.
+    pub fn only_if(mut self, guard: &str) -> Command<H> {
+        self.guards.push(Guard::OnlyIf(guard.into()));
+        self
+    }
+
     /// Execute the command.
     ///
+    ///## Idempotence
+    ///
+    /// Commands with guards (see `creates()`) are idempotent, which is
+    /// represented by the type `Future<Item = Option<..>, ...>`. Thus if it
+    /// returns `Option::None` then a guard determined that the command
+    /// doesn't need to run, and if it returns `Option::Some` then the
+    /// command is running. Commands without guards always run.
+    ///
     ///## Returns
     ///
     /// This function returns a `Future` that represents the delay between
     /// now and the time it takes to start execution. This `Future` yields a
-    /// tuple with a `Stream` and a `Future` inside. The `Stream` is the
-    /// command's output stream, including both stdout and stderr. The `Future`
-    /// yields the command's `ExitStatus`.
+    /// `CommandStatus`, which holds the command's output stream, including
+    /// both stdout and stderr, and is itself a `Future` that yields the
+    /// command's `ExitStatus`.
     ///
     /// **WARNING!** For remote `Host` types, you _MUST_ consume the output
     /// `Stream` if you want to access the `ExitStatus`. This is due to the
@@ -254,84 +721,640 @@ impl<H: Host + 'static> Command<H> {
     ///
     /// This is the error you'll see if you prematurely drop the output `Stream`
     /// while trying to resolve the `Future<Item = ExitStatus, ...>`.
-    pub fn exec(&self) -> Box<Future<Item = CommandStatus, Error = Error>> {
-        let request = Request::CommandExec(self.provider, self.cmd.clone());
-        Box::new(self.host.request(request)
-            .chain_err(|| ErrorKind::Request { endpoint: "Command", func: "exec" })
-            .map(|msg| {
-                CommandStatus::new(msg)
+    ///
+    /// If the command fails part way through, e.g. because its output could
+    /// not be read, the output `Stream` and the `ExitStatus` future will both
+    /// resolve to the error that caused it.
+    pub fn exec(&self) -> Box<Future<Item = Option<CommandStatus>, Error = Error>> {
+        if self.guards.is_empty() {
+            return Box::new(self.run().map(Some));
+        }
+
+        let cmd = self.clone();
+        Box::new(self.check_guards()
+            .chain_err(|| ErrorKind::Request { endpoint: "Command".into(), func: "exec".into() })
+            .and_then(move |run| if run {
+                Box::new(cmd.run().map(Some)) as Box<Future<Item = _, Error = Error>>
+            } else {
+                Box::new(future::ok(None))
+            }))
+    }
+
+    // Resolves to whether every guard allows the command to run
+    fn check_guards(&self) -> Box<Future<Item = bool, Error = Error>> {
+        let mut check: Box<Future<Item = bool, Error = Error>> = Box::new(future::ok(true));
+
+        for guard in &self.guards {
+            // Guards run in the same environment as the command
+            let mut options = self.options.clone();
+            options.pty = None;
+
+            let (cmd, run_on_success) = match *guard {
+                Guard::Creates(ref path) => (vec!["test", "-e", path], false),
+                Guard::Removes(ref path) => (vec!["test", "-e", path], true),
+                Guard::Unless(ref cmd) => (DEFAULT_SHELL.iter().cloned().chain(Some(&**cmd)).collect(), false),
+                Guard::OnlyIf(ref cmd) => (DEFAULT_SHELL.iter().cloned().chain(Some(&**cmd)).collect(), true),
+            };
+            options.shell = matches!(*guard, Guard::Unless(_) | Guard::OnlyIf(_));
+
+            let guard = Command {
+                host: self.host.clone(),
+                provider: self.provider,
+                cmd: cmd.into_iter().map(|a| a.to_owned()).collect(),
+                options: options,
+                stdin: None,
+                guards: Vec::new(),
+            };
+
+            check = Box::new(check.and_then(move |run| if run {
+                Box::new(guard.run().and_then(|mut status| {
+                        // Drain the output so that we can get to the exit
+                        // status.
+                        status.take_stream().unwrap()
+                            .for_each(|_| Ok(()))
+                            .join(status)
+                    })
+                    .map(move |(_, status)| status.success == run_on_success)) as Box<Future<Item = _, Error = Error>>
+            } else {
+                Box::new(future::ok(false))
+            }));
+        }
+
+        check
+    }
+
+    fn run(&self) -> Box<Future<Item = CommandStatus, Error = Error>> {
+        let request = Request::CommandExec(self.provider, self.cmd.clone(), self.options.clone());
+        let msg = match self.stdin {
+            Some(ref input) => {
+                let (tx, body) = Body::pair();
+                let chunks: Vec<_> = input.chunks(STDIN_CHUNK_SIZE).map(|c| Ok(c.to_vec())).collect();
+                self.host.handle().spawn(tx.send_all(stream::iter_ok(chunks))
+                    .map(|_| ())
+                    .map_err(|_| ()));
+                Message::WithBody(request, body)
+            },
+            None => Message::WithoutBody(request),
+        };
+
+        let host = self.host.clone();
+        Box::new(self.host.request_msg(msg)
+            .chain_err(|| ErrorKind::Request { endpoint: "Command".into(), func: "exec".into() })
+            .and_then(move |msg| {
+                CommandStatus::new(&host, msg)
             }))
     }
 }
 
 impl CommandStatus {
     #[doc(hidden)]
-    pub fn new(mut msg: Message<Response, Body<Vec<u8>, io::Error>>) -> CommandStatus {
-        let (tx, rx) = oneshot::channel::<ExitStatus>();
+    pub fn new<H: Host + 'static>(host: &H, mut msg: Message<Response, Body<Vec<u8>, io::Error>>) -> Result<CommandStatus> {
+        let body = match msg.take_body() {
+            Some(b) => b,
+            None => return Err("Command::exec reply missing body stream".into()),
+        };
+        let pid = match *msg.get_ref() {
+            Response::Pid(pid) => Some(pid),
+            _ => None,
+        };
+
+        let host = host.clone();
+        let control = move |request: Request, func: &'static str| -> Box<Future<Item = (), Error = Error>> {
+            if !host.supports(request.name()) {
+                return Box::new(future::err(ErrorKind::Unsupported(request.name().into()).into()));
+            }
+
+            // Remote hosts reply to requests in order, so the reply to this
+            // one is stuck behind the command's output until the command
+            // exits. Rather than wait for it, send the request and move on.
+            host.handle().spawn(host.request(request)
+                .chain_err(move || ErrorKind::Request { endpoint: "Command".into(), func: func.into() })
+                .map(|_| ())
+                .map_err(|e| warn!("{}", e.display_chain())));
+            Box::new(future::ok(()))
+        };
+
+        let (tx, rx) = oneshot::channel::<result::Result<ExitStatus, serializable::Error>>();
         let mut tx = Some(tx);
-        let stream = msg.take_body()
-            .expect("Command::exec reply missing body stream")
-            .filter_map(move |v| {
-                let s = String::from_utf8_lossy(&v).to_string();
-
-                // @todo This is a heuristical approach which is fallible
-                if s.starts_with("ExitStatus:") {
-                    let (_, json) = s.split_at(11);
-                    match serde_json::from_str(json) {
-                        Ok(status) => {
-                            // @todo What should happen if this fails?
-                            let _ = tx.take().unwrap().send(status);
-                            return None;
-                        },
-                        _ => (),
-                    }
+        let stream = body
+            .then(move |r| {
+                let bytes = r.chain_err(|| "Command execution failed")?;
+                match Chunk::from_bytes(bytes)? {
+                    Chunk::Stdout(b) => Ok(Some(Output::Stdout(String::from_utf8_lossy(&b).into_owned()))),
+                    Chunk::Stderr(b) => Ok(Some(Output::Stderr(String::from_utf8_lossy(&b).into_owned()))),
+                    Chunk::ExitStatus(status) => {
+                        if let Some(tx) = tx.take() {
+                            let _ = tx.send(Ok(status));
+                        }
+                        Ok(None)
+                    },
+                    // If the command fails part way through, the error is
+                    // sent instead of the exit status.
+                    Chunk::Error(e) => {
+                        if let Some(tx) = tx.take() {
+                            let _ = tx.send(Err(e.clone()));
+                        }
+                        Err(e.into())
+                    },
                 }
-
-                Some(s)
             })
-            .then(|r| r.chain_err(|| "Command execution failed"));
+            .filter_map(|s| s);
 
-        let exit_status = rx.chain_err(|| "Buffer dropped before ExitStatus was sent");
+        let exit_status = rx.chain_err(|| "Buffer dropped before ExitStatus was sent")
+            .and_then(|r| r.map_err(|e| e.into()));
 
-        CommandStatus {
+        Ok(CommandStatus {
             stream: Some(Box::new(stream)),
+            split: None,
             exit_status: Some(Box::new(exit_status)),
+            pid: pid,
+            limit: None,
+            control: Box::new(control),
+        })
+    }
+
+    /// The command's process ID on its host, if known.
+    ///
+    /// Commands can only be controlled over the connection that started
+    /// them, so this can't be used to signal it from another `Host`.
+    pub fn pid(&self) -> Option<u32> {
+        self.pid
+    }
+
+    /// Cancel the command.
+    ///
+    /// The command is first sent SIGTERM, then SIGKILL if it still hasn't
+    /// exited after a grace period. Its output stream and `ExitStatus` then
+    /// resolve to an `ErrorKind::CommandCancelled` error.
+    ///
+    /// The returned `Future` resolves as soon as the request has been sent,
+    /// as remote hosts won't reply until the command has exited. If the
+    /// request fails, e.g. because the command had already exited, the
+    /// error is logged.
+    ///
+    /// Note that dropping `CommandStatus` does _not_ stop the command.
+    pub fn cancel(&self) -> Box<Future<Item = (), Error = Error>> {
+        match self.pid {
+            Some(pid) => (self.control)(Request::CommandCancel(None, pid), "cancel"),
+            None => Box::new(future::err("Command has no process to cancel".into())),
+        }
+    }
+
+    /// Resize the command's PTY. This only works for commands that were
+    /// started with `Command::pty()`.
+    ///
+    /// The command receives SIGWINCH to let it know. As with `cancel()`, the
+    /// returned `Future` resolves as soon as the request has been sent and
+    /// any errors are logged.
+    pub fn resize(&self, rows: u16, cols: u16) -> Box<Future<Item = (), Error = Error>> {
+        match self.pid {
+            Some(pid) => (self.control)(Request::CommandResize(None, pid, WindowSize { rows: rows, cols: cols }), "resize"),
+            None => Box::new(future::err("Command has no process to resize".into())),
+        }
+    }
+
+    /// Send a signal to the command.
+    ///
+    /// The signal is sent to the command's process group, so it reaches the
+    /// command itself rather than just the shell that runs it. As with
+    /// `cancel()`, the returned `Future` resolves as soon as the request has
+    /// been sent and any errors are logged.
+    ///
+    ///## Example
+    ///```no_run
+    ///extern crate futures;
+    ///extern crate intecture_api;
+    ///extern crate tokio_core;
+    ///
+    ///use futures::Future;
+    ///use intecture_api::command::Signal;
+    ///use intecture_api::prelude::*;
+    ///use tokio_core::reactor::Core;
+    ///
+    ///# fn main() {
+    ///let mut core = Core::new().unwrap();
+    ///let handle = core.handle();
+    ///
+    ///let host = Local::new(&handle).wait().unwrap();
+    ///
+    ///let cmd = Command::new(&host, "tail -f /var/log/syslog", None);
+    ///let result = cmd.exec().and_then(|status| {
+    ///    let status = status.unwrap();
+    ///    println!("Started tail with PID {:?}", status.pid());
+    ///    status.signal(Signal::Term)
+    ///});
+    ///
+    ///core.run(result).unwrap();
+    ///# }
+    ///```
+    pub fn signal(&self, signal: Signal) -> Box<Future<Item = (), Error = Error>> {
+        match self.pid {
+            Some(pid) => (self.control)(Request::CommandSignal(None, pid, signal), "signal"),
+            None => Box::new(future::err("Command has no process to signal".into())),
         }
     }
 
-    /// Take ownership of the output stream.
+    /// Take ownership of the output stream, which combines stdout and stderr
+    /// in the order they were received. Each line is tagged with the stream
+    /// it came from.
     ///
     /// The stream is guaranteed to be present only if this is the first call
-    /// to `take_stream()` and the future has not yet been polled.
-    pub fn take_stream(&mut self) -> Option<Box<Stream<Item = String, Error = Error>>> {
+    /// to `take_stream()`, neither `take_stdout()` nor `take_stderr()` have
+    /// been called, and the future has not yet been polled.
+    pub fn take_stream(&mut self) -> Option<Box<Stream<Item = Output, Error = Error>>> {
         self.stream.take()
     }
 
-    /// Convert this to a `CommandResult`, which returns the output string on
-    /// success and an error containing the command's output on failure. If the
-    /// stream has already been taken by `take_stream()` then this function
-    /// will return `None`.
+    /// Take ownership of the stdout stream.
+    ///
+    /// You can take both the stdout and stderr streams, but not the combined
+    /// stream from `take_stream()`. If you only take one of them, the other's
+    /// output is discarded.
+    pub fn take_stdout(&mut self) -> Option<Box<Stream<Item = String, Error = Error>>> {
+        self.take_split(true)
+    }
+
+    /// Take ownership of the stderr stream.
+    ///
+    /// You can take both the stdout and stderr streams, but not the combined
+    /// stream from `take_stream()`. If you only take one of them, the other's
+    /// output is discarded.
+    pub fn take_stderr(&mut self) -> Option<Box<Stream<Item = String, Error = Error>>> {
+        self.take_split(false)
+    }
+
+    fn take_split(&mut self, stdout: bool) -> Option<Box<Stream<Item = String, Error = Error>>> {
+        if self.split.is_none() {
+            let stream = self.stream.take()?;
+            self.split = Some(Rc::new(RefCell::new(Split {
+                stream: stream,
+                done: false,
+                stdout: None,
+                stderr: None,
+                stdout_task: None,
+                stderr_task: None,
+            })));
+        }
+
+        let split = self.split.as_ref().unwrap();
+        {
+            let mut s = split.borrow_mut();
+            let buffer = if stdout { &mut s.stdout } else { &mut s.stderr };
+            if buffer.is_some() {
+                return None;
+            }
+            *buffer = Some(VecDeque::new());
+        }
+
+        Some(Box::new(SplitStream {
+            split: split.clone(),
+            stdout: stdout,
+        }))
+    }
+
+    /// Convert this to a `CommandResult`, which returns the command's output
+    /// on success and an error containing the command's output on failure. If
+    /// the stream has already been taken by `take_stream()`, `take_stdout()`
+    /// or `take_stderr()` then this function will return `None`.
     ///
     /// Note that "success" is determined by examining the `ExitStatus::success`
     /// bool. See `ExitStatus` docs for details.
+    ///
+    /// All output is kept in memory, unless it's been limited with
+    /// `limit_output()`, so for commands that may print a lot, use
+    /// `result_with()` instead.
     pub fn result(self) -> Option<CommandResult> {
-        if let Some(stream) = self.stream {
-            let inner = stream.fold(String::new(), |mut acc, line| {
-                    acc.push_str(&line);
-                    future::ok::<_, Error>(acc)
-                })
-                .join(self.exit_status.unwrap())
-                .and_then(|(output, status)| if status.success {
-                    future::ok(output)
-                } else {
-                    future::err(ErrorKind::Command(output).into())
-                });
+        let capture = match self.limit {
+            Some(max) => Capture::Tail(max),
+            None => Capture::All,
+        };
+        self.result_with(capture)
+    }
 
-            Some(CommandResult {
-                inner: Box::new(inner) as Box<Future<Item = String, Error = Error>>
+    /// Limit `result()` to the last `max` bytes of stdout and stderr each.
+    /// This is the same as `result_with(Capture::Tail(max))`.
+    pub fn limit_output(mut self, max: usize) -> CommandStatus {
+        self.limit = Some(max);
+        self
+    }
+
+    /// Like `result()`, but `capture` decides how much of the command's
+    /// output is kept, and where.
+    ///
+    ///## Example
+    ///```no_run
+    ///extern crate futures;
+    ///extern crate intecture_api;
+    ///extern crate tokio_core;
+    ///
+    ///use futures::Future;
+    ///use intecture_api::command::Capture;
+    ///use intecture_api::prelude::*;
+    ///use tokio_core::reactor::Core;
+    ///
+    ///# fn main() {
+    ///let mut core = Core::new().unwrap();
+    ///let handle = core.handle();
+    ///
+    ///let host = Local::new(&handle).wait().unwrap();
+    ///
+    ///let cmd = Command::new(&host, "make", None);
+    ///let result = cmd.exec().and_then(|status| {
+    ///    // Keep the last 64 KiB of output, which is where the errors are
+    ///    status.unwrap().result_with(Capture::Tail(64 * 1024)).unwrap()
+    ///});
+    ///
+    ///core.run(result).unwrap();
+    ///# }
+    ///```
+    pub fn result_with(self, capture: Capture) -> Option<CommandResult> {
+        let stream = self.stream?;
+        let exit_status = self.exit_status.unwrap();
+
+        let captured = match Captured::new(capture) {
+            Ok(c) => c,
+            Err(e) => return Some(CommandResult { inner: Box::new(future::err(e)) }),
+        };
+
+        let inner = stream.fold(captured, |mut captured, output| {
+                captured.push(output).map(|_| captured)
             })
-        } else {
-            None
+            .join(exit_status)
+            .and_then(|(captured, status)| {
+                let output = captured.finish()?;
+                if status.success {
+                    Ok(output)
+                } else {
+                    Err(ErrorKind::Command {
+                        stdout: output.stdout,
+                        stderr: output.stderr,
+                        stdout_file: output.stdout_file,
+                        stderr_file: output.stderr_file,
+                    }.into())
+                }
+            });
+
+        Some(CommandResult {
+            inner: Box::new(inner) as Box<Future<Item = CommandOutput, Error = Error>>
+        })
+    }
+}
+
+impl Captured {
+    fn new(capture: Capture) -> Result<Captured> {
+        let files = match capture {
+            Capture::TempFile => Some((
+                BufWriter::new(Builder::new().prefix("intecture-stdout-").tempfile()
+                    .chain_err(|| "Could not create file for command output")?),
+                BufWriter::new(Builder::new().prefix("intecture-stderr-").tempfile()
+                    .chain_err(|| "Could not create file for command output")?),
+            )),
+            _ => None,
+        };
+
+        Ok(Captured {
+            capture: capture,
+            output: CommandOutput::default(),
+            files: files,
+        })
+    }
+
+    fn push(&mut self, output: Output) -> Result<()> {
+        match self.capture {
+            Capture::All => match output {
+                Output::Stdout(line) => self.output.stdout.push_str(&line),
+                Output::Stderr(line) => self.output.stderr.push_str(&line),
+            },
+            Capture::Head(max) => {
+                let (buf, line) = match output {
+                    Output::Stdout(line) => (&mut self.output.stdout, line),
+                    Output::Stderr(line) => (&mut self.output.stderr, line),
+                };
+                if buf.len() + line.len() > max {
+                    let end = floor_char_boundary(&line, max - buf.len());
+                    buf.push_str(&line[..end]);
+                    self.output.truncated = true;
+                } else {
+                    buf.push_str(&line);
+                }
+            },
+            Capture::Tail(max) => {
+                let buf = match output {
+                    Output::Stdout(line) => { self.output.stdout.push_str(&line); &mut self.output.stdout },
+                    Output::Stderr(line) => { self.output.stderr.push_str(&line); &mut self.output.stderr },
+                };
+                // Let the buffer grow a little before trimming it, so we
+                // aren't shuffling it along for every line.
+                if buf.len() > max.saturating_mul(2) {
+                    trim_front(buf, max);
+                    self.output.truncated = true;
+                }
+            },
+            Capture::TempFile => {
+                let (ref mut stdout, ref mut stderr) = *self.files.as_mut().unwrap();
+                let (file, line) = match output {
+                    Output::Stdout(line) => (stdout, line),
+                    Output::Stderr(line) => (stderr, line),
+                };
+                file.write_all(line.as_bytes())
+                    .chain_err(|| "Could not write command output to file")?;
+            },
+            Capture::Lines(ref mut f) => f(output),
+        }
+
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<CommandOutput> {
+        if let Capture::Tail(max) = self.capture {
+            for buf in &mut [&mut self.output.stdout, &mut self.output.stderr] {
+                if buf.len() > max {
+                    trim_front(buf, max);
+                    self.output.truncated = true;
+                }
+            }
+        }
+
+        if let Some((stdout, stderr)) = self.files {
+            self.output.stdout_file = Some(keep_file(stdout)?);
+            self.output.stderr_file = Some(keep_file(stderr)?);
+        }
+
+        Ok(self.output)
+    }
+}
+
+// Flush a temporary file and stop it from being deleted when dropped
+fn keep_file(file: BufWriter<NamedTempFile>) -> Result<PathBuf> {
+    let file = file.into_inner()
+        .map_err(|e| e.into_error())
+        .chain_err(|| "Could not write command output to file")?;
+    file.into_temp_path().keep()
+        .chain_err(|| "Could not write command output to file")
+}
+
+// Drop bytes from the front of `buf` until at most `max` remain, without
+// splitting a character.
+fn trim_front(buf: &mut String, max: usize) {
+    let mut start = buf.len() - max;
+    while !buf.is_char_boundary(start) {
+        start += 1;
+    }
+    buf.drain(..start);
+}
+
+// The largest character boundary in `s` that isn't past `index`
+fn floor_char_boundary(s: &str, mut index: usize) -> usize {
+    if index >= s.len() {
+        return s.len();
+    }
+    while !s.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+impl fmt::Display for Output {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Output::Stdout(ref line) | Output::Stderr(ref line) => write!(f, "{}", line),
+        }
+    }
+}
+
+impl Stream for SplitStream {
+    type Item = String;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        let mut split = self.split.borrow_mut();
+
+        loop {
+            let line = if self.stdout {
+                split.stdout.as_mut().unwrap().pop_front()
+            } else {
+                split.stderr.as_mut().unwrap().pop_front()
+            };
+            if line.is_some() {
+                return Ok(Async::Ready(line));
+            }
+
+            if split.done {
+                return Ok(Async::Ready(None));
+            }
+
+            match split.stream.poll() {
+                Ok(Async::Ready(Some(Output::Stdout(line)))) => if let Some(ref mut b) = split.stdout {
+                    b.push_back(line);
+                    if let Some(ref t) = split.stdout_task {
+                        t.notify();
+                    }
+                },
+                Ok(Async::Ready(Some(Output::Stderr(line)))) => if let Some(ref mut b) = split.stderr {
+                    b.push_back(line);
+                    if let Some(ref t) = split.stderr_task {
+                        t.notify();
+                    }
+                },
+                Ok(Async::Ready(None)) => {
+                    split.done = true;
+                    // Make sure the other half sees the end of the stream
+                    for t in split.stdout_task.iter().chain(split.stderr_task.iter()) {
+                        t.notify();
+                    }
+                },
+                Ok(Async::NotReady) => {
+                    // The other half may be polled from a different task, so
+                    // remember who to wake when we buffer output for them.
+                    if self.stdout {
+                        split.stdout_task = Some(task::current());
+                    } else {
+                        split.stderr_task = Some(task::current());
+                    }
+                    return Ok(Async::NotReady);
+                },
+                Err(e) => {
+                    split.done = true;
+                    return Err(e);
+                },
+            }
+        }
+    }
+}
+
+impl Chunk {
+    pub fn into_bytes(self) -> Result<Vec<u8>> {
+        let (tag, payload) = match self {
+            Chunk::Stdout(b) => (CHUNK_STDOUT, b),
+            Chunk::Stderr(b) => (CHUNK_STDERR, b),
+            Chunk::ExitStatus(s) => (CHUNK_EXIT_STATUS, serde_json::to_vec(&s)
+                .chain_err(|| "Could not serialize `ExitStatus` struct")?),
+            Chunk::Error(e) => (CHUNK_ERROR, serde_json::to_vec(&e)
+                .chain_err(|| "Could not serialize command error")?),
+        };
+
+        let mut bytes = Vec::with_capacity(payload.len() + 1);
+        bytes.push(tag);
+        bytes.extend(payload);
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Chunk> {
+        let (tag, payload) = match bytes.split_first() {
+            Some((t, p)) => (*t, p),
+            None => bail!("Command output contained an empty chunk"),
+        };
+
+        Ok(match tag {
+            CHUNK_STDOUT => Chunk::Stdout(payload.to_vec()),
+            CHUNK_STDERR => Chunk::Stderr(payload.to_vec()),
+            CHUNK_EXIT_STATUS => Chunk::ExitStatus(serde_json::from_slice(payload)
+                .chain_err(|| "Could not deserialize `ExitStatus` struct")?),
+            CHUNK_ERROR => Chunk::Error(serde_json::from_slice(payload)
+                .chain_err(|| "Could not deserialize command error")?),
+            t => bail!("Command output contained an unknown chunk type {}", t),
+        })
+    }
+}
+
+impl Signal {
+    // Translate to the host's signal number.
+    fn to_raw(self) -> i32 {
+        match self {
+            Signal::Hup => libc::SIGHUP,
+            Signal::Int => libc::SIGINT,
+            Signal::Quit => libc::SIGQUIT,
+            Signal::Kill => libc::SIGKILL,
+            Signal::Usr1 => libc::SIGUSR1,
+            Signal::Usr2 => libc::SIGUSR2,
+            Signal::Pipe => libc::SIGPIPE,
+            Signal::Alrm => libc::SIGALRM,
+            Signal::Term => libc::SIGTERM,
+            Signal::Cont => libc::SIGCONT,
+            Signal::Stop => libc::SIGSTOP,
+            Signal::Tstp => libc::SIGTSTP,
+            Signal::Winch => libc::SIGWINCH,
+            Signal::Other(n) => n,
+        }
+    }
+
+    // Translate from the host's signal number.
+    fn from_raw(n: i32) -> Signal {
+        match n {
+            libc::SIGHUP => Signal::Hup,
+            libc::SIGINT => Signal::Int,
+            libc::SIGQUIT => Signal::Quit,
+            libc::SIGKILL => Signal::Kill,
+            libc::SIGUSR1 => Signal::Usr1,
+            libc::SIGUSR2 => Signal::Usr2,
+            libc::SIGPIPE => Signal::Pipe,
+            libc::SIGALRM => Signal::Alrm,
+            libc::SIGTERM => Signal::Term,
+            libc::SIGCONT => Signal::Cont,
+            libc::SIGSTOP => Signal::Stop,
+            libc::SIGTSTP => Signal::Tstp,
+            libc::SIGWINCH => Signal::Winch,
+            n => Signal::Other(n),
         }
     }
 }
@@ -352,10 +1375,108 @@ impl Future for CommandStatus {
 }
 
 impl Future for CommandResult {
-    type Item = String;
+    type Item = CommandOutput;
     type Error = Error;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
         self.inner.poll()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::fs;
+    use std::rc::Rc;
+    use super::{Capture, Captured, CommandOutput, Output};
+
+    fn capture(capture: Capture, output: &[Output]) -> CommandOutput {
+        let mut captured = Captured::new(capture).unwrap();
+        for o in output {
+            captured.push(o.clone()).unwrap();
+        }
+        captured.finish().unwrap()
+    }
+
+    fn output() -> Vec<Output> {
+        vec![
+            Output::Stdout("line1\n".into()),
+            Output::Stderr("éé1".into()),
+            Output::Stdout("line2\n".into()),
+            Output::Stderr("éé2".into()),
+            Output::Stdout("line3\n".into()),
+        ]
+    }
+
+    #[test]
+    fn test_capture_all() {
+        let out = capture(Capture::All, &output());
+        assert_eq!(out.stdout, "line1\nline2\nline3\n");
+        assert_eq!(out.stderr, "éé1éé2");
+        assert!(!out.truncated);
+    }
+
+    #[test]
+    fn test_capture_head() {
+        let out = capture(Capture::Head(8), &output());
+        assert_eq!(out.stdout, "line1\nli");
+        // "é" is two bytes, so we can't stop half way through it
+        assert_eq!(out.stderr, "éé1é");
+        assert!(out.truncated);
+
+        let out = capture(Capture::Head(100), &output());
+        assert_eq!(out.stdout, "line1\nline2\nline3\n");
+        assert!(!out.truncated);
+    }
+
+    #[test]
+    fn test_capture_tail() {
+        let out = capture(Capture::Tail(8), &output());
+        assert_eq!(out.stdout, "2\nline3\n");
+        assert_eq!(out.stderr, "é1éé2");
+        assert!(out.truncated);
+
+        // Rather than split a character, we keep less than we could
+        let out = capture(Capture::Tail(7), &output());
+        assert_eq!(out.stderr, "1éé2");
+
+        let out = capture(Capture::Tail(100), &output());
+        assert_eq!(out.stdout, "line1\nline2\nline3\n");
+        assert!(!out.truncated);
+    }
+
+    #[test]
+    fn test_capture_tail_many_lines() {
+        let lines: Vec<Output> = (0..1000).map(|i| Output::Stdout(format!("{}\n", i))).collect();
+        let out = capture(Capture::Tail(8), &lines);
+        assert_eq!(out.stdout, "998\n999\n");
+        assert!(out.truncated);
+    }
+
+    #[test]
+    fn test_capture_temp_file() {
+        let out = capture(Capture::TempFile, &output());
+        let stdout_file = out.stdout_file.unwrap();
+        let stderr_file = out.stderr_file.unwrap();
+        let stdout = fs::read_to_string(&stdout_file).unwrap();
+        let stderr = fs::read_to_string(&stderr_file).unwrap();
+        fs::remove_file(stdout_file).unwrap();
+        fs::remove_file(stderr_file).unwrap();
+
+        // The files hold exactly what `Capture::All` would have kept
+        let all = capture(Capture::All, &output());
+        assert_eq!(stdout, all.stdout);
+        assert_eq!(stderr, all.stderr);
+        assert!(out.stdout.is_empty());
+    }
+
+    #[test]
+    fn test_capture_lines() {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let seen_clone = seen.clone();
+        let out = capture(Capture::Lines(Box::new(move |o| seen_clone.borrow_mut().push(o))), &output());
+        assert_eq!(*seen.borrow(), output());
+        assert!(out.stdout.is_empty());
+        assert!(out.stderr.is_empty());
+    }
+}
@@ -5,85 +5,644 @@
 // modified, or distributed except according to those terms.
 
 use errors::*;
-use futures::{future, Future};
+use futures::{future, Async, Future, Poll};
+use futures::future::Either;
 use futures::sink::Sink;
 use futures::stream::Stream;
-use futures::sync::mpsc;
+use futures::sync::oneshot;
+use libc;
 use remote::{ExecutableResult, Response, ResponseResult};
-use serde_json;
-use std::io::{self, BufReader};
-use std::process::{Command, Stdio};
-use std::result;
-use super::{CommandProvider, ExitStatus};
-use tokio_core::reactor::Handle;
-use tokio_io::io::lines;
-use tokio_process::CommandExt;
+use std::{cmp, mem, str};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::ffi::{CStr, CString};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, Read};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::process::{CommandExt as UnixCommandExt, ExitStatusExt};
+use std::process::{self, Command, Stdio};
+use std::rc::Rc;
+use std::time::Duration;
+use super::{Chunk, CommandProvider, Escalation, ExecOptions, ExitStatus, Signal, WindowSize};
+use tokio_core::reactor::{Handle, PollEvented, Timeout};
+use tokio_file_unix;
+use tokio_io::{AsyncRead, AsyncWrite};
+use tokio_io::io::{lines, write_all, ReadHalf};
+use tokio_process::{Child, CommandExt};
 use tokio_proto::streaming::{Body, Message};
+use users::{self, get_effective_gid, get_effective_uid};
+use users::os::unix::UserExt;
+
+/// Seconds that a command has to exit after SIGTERM, before it's killed
+const KILL_GRACE_PERIOD: u64 = 5;
+
+thread_local! {
+    // The session that is executing the current request, if any
+    static SESSION: RefCell<Option<Processes>> = const { RefCell::new(None) };
+}
 
 pub struct Generic;
 
+/// Commands that are still running, keyed by PID, so they can be signalled,
+/// cancelled or resized. Each session has its own, so that a client can only
+/// touch the commands that it started.
+#[derive(Clone, Default)]
+pub struct Processes(Rc<RefCell<BTreeMap<u32, Running>>>);
+
+struct Running {
+    // Taken once the command has been cancelled
+    cancel: Option<oneshot::Sender<()>>,
+    // Our own handle on the command's PTY, so that it can be resized
+    pty: Option<File>,
+}
+
+// Removes a command from its session's registry when dropped, so that the
+// entry goes away however we stop waiting for the command.
+struct Registered {
+    processes: Processes,
+    pid: u32,
+}
+
+type Pty = PollEvented<tokio_file_unix::File<File>>;
+
+// Reads the raw output of a PTY. Reads are split on character boundaries so
+// that multibyte characters survive the trip to the client.
+struct PtyOutput {
+    master: ReadHalf<Pty>,
+    buf: Vec<u8>,
+    done: bool,
+}
+
+// Who a command runs as, and how we get there.
+enum Identity {
+    // Run as whoever we are already
+    Current,
+    // We're root, so we can switch to anyone
+    Switch {
+        uid: u32,
+        gid: u32,
+        user: Option<users::User>,
+    },
+    // Ask sudo or doas to switch for us, using this argv prefix
+    Escalate(Vec<String>),
+}
+
+impl Processes {
+    /// Run `f` on behalf of this session, so that any commands it starts or
+    /// controls are looked up here.
+    pub fn enter<F, R>(&self, f: F) -> R
+        where F: FnOnce() -> R
+    {
+        let previous = SESSION.with(|s| s.borrow_mut().replace(self.clone()));
+        let result = f();
+        SESSION.with(|s| *s.borrow_mut() = previous);
+        result
+    }
+
+    // Commands started outside of a session can't be controlled later, so
+    // they get a registry of their own.
+    fn current() -> Processes {
+        SESSION.with(|s| s.borrow().clone()).unwrap_or_default()
+    }
+
+    // Cancel a running command, returning false if there isn't one
+    fn cancel(&self, pid: u32) -> bool {
+        match self.0.borrow_mut().get_mut(&pid) {
+            Some(running) => {
+                // If the command is already being cancelled, there's nothing
+                // more to do.
+                if let Some(tx) = running.cancel.take() {
+                    let _ = tx.send(());
+                }
+                true
+            },
+            None => false,
+        }
+    }
+}
+
+impl Drop for Registered {
+    fn drop(&mut self) {
+        self.processes.0.borrow_mut().remove(&self.pid);
+    }
+}
+
 impl CommandProvider for Generic {
     fn available() -> bool {
         true
     }
 
-    fn exec(&self, handle: &Handle, cmd: &[&str]) -> ExecutableResult {
-        let (cmd, cmd_args) = match cmd.split_first() {
+    fn exec_with(&self, handle: &Handle, cmd: &[&str], options: &ExecOptions, stdin: Option<Body<Vec<u8>, io::Error>>) -> ExecutableResult {
+        let identity = match identity(options) {
+            Ok(i) => i,
+            Err(e) => return Box::new(future::err(e)),
+        };
+
+        let mut argv: Vec<&str> = Vec::new();
+        if let Identity::Escalate(ref prefix) = identity {
+            argv.extend(prefix.iter().map(|a| &**a));
+        }
+        argv.extend_from_slice(cmd);
+
+        let (cmd, cmd_args) = match argv.split_first() {
             Some((s, a)) => (s, a),
             None => return Box::new(future::err("Invalid shell provided".into())),
         };
 
-        let child = Command::new(cmd)
-            .args(cmd_args)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn_async(handle)
+        let mut command = Command::new(cmd);
+        command.args(cmd_args);
+
+        // Either way, the command gets its own process group, so that if we
+        // have to terminate it, we can take its children with it.
+        let master = match options.pty {
+            Some(ref size) => match attach_pty(&mut command, size) {
+                Ok(m) => Some(m),
+                Err(e) => return Box::new(future::err(e)),
+            },
+            None => {
+                command.stdout(Stdio::piped())
+                    .stderr(Stdio::piped());
+                unsafe {
+                    command.pre_exec(|| {
+                        if libc::setpgid(0, 0) != 0 {
+                            return Err(io::Error::last_os_error());
+                        }
+                        Ok(())
+                    });
+                }
+                None
+            },
+        };
+
+        if options.env_clear {
+            command.env_clear();
+        }
+        if let Identity::Switch { uid, gid, ref user } = identity {
+            // Supplementary groups are looked up now, as it isn't safe to do
+            // so between fork and exec.
+            let groups = match *user {
+                Some(ref u) => {
+                    command.env("HOME", u.home_dir())
+                        .env("USER", u.name())
+                        .env("LOGNAME", u.name());
+                    match group_list(u.name(), gid) {
+                        Ok(g) => Some(g),
+                        Err(e) => return Box::new(future::err(e)),
+                    }
+                },
+                None => None,
+            };
+
+            unsafe {
+                command.pre_exec(move || {
+                    if let Some(ref groups) = groups {
+                        if libc::setgroups(groups.len() as _, groups.as_ptr()) != 0 {
+                            return Err(io::Error::last_os_error());
+                        }
+                    }
+                    if libc::setgid(gid) != 0 || libc::setuid(uid) != 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+        }
+        for (k, v) in &options.env {
+            command.env(k, v);
+        }
+        if let Some(ref cwd) = options.cwd {
+            command.current_dir(cwd);
+        }
+        if let Some(mask) = options.umask {
+            // umask() can't fail and is async-signal-safe, so it's fine to
+            // call between fork and exec.
+            unsafe {
+                command.pre_exec(move || {
+                    libc::umask(mask as libc::mode_t);
+                    Ok(())
+                });
+            }
+        }
+        // Without a body, don't let the command inherit our stdin, which in
+        // `--stdio` mode is the agent's connection to the client.
+        if master.is_none() {
+            command.stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() });
+        }
+
+        // Keep a handle on the PTY for resizing, and make the master
+        // nonblocking so we can read it asynchronously.
+        let (pty, master) = match master {
+            Some(m) => {
+                let pty = match m.try_clone() {
+                    Ok(p) => p,
+                    Err(e) => return Box::new(future::err(Error::with_chain(e, "Could not open PTY"))),
+                };
+                match tokio_file_unix::File::new_nb(m).and_then(|m| m.into_io(handle)) {
+                    Ok(m) => (Some(pty), Some(m)),
+                    Err(e) => return Box::new(future::err(Error::with_chain(e, "Could not open PTY"))),
+                }
+            },
+            None => (None, None),
+        };
+
+        let timeout = match options.timeout {
+            Some(t) => match Timeout::new(t, handle) {
+                Ok(timeout) => Some((t, timeout)),
+                Err(e) => return Box::new(future::err(Error::with_chain(e, "Could not start command timeout"))),
+            },
+            None => None,
+        };
+
+        let child = command.spawn_async(handle)
             .chain_err(|| "Command execution failed");
         let mut child = match child {
             Ok(c) => c,
             Err(e) => return Box::new(future::err(e)),
         };
 
+        // Close our copies of the PTY's slave side, so that reading the
+        // master ends when the command exits.
+        drop(command);
+
+        let pid = child.id();
+        let (cancel_tx, cancel_rx) = oneshot::channel();
+        let processes = Processes::current();
+        processes.0.borrow_mut().insert(pid, Running {
+            cancel: Some(cancel_tx),
+            pty: pty,
+        });
+
+        // Resolves with the reason for stopping the command early, if any.
+        // If the command finishes first, the sender is dropped and we're
+        // left waiting forever, which is fine.
+        let mut stop: Box<Future<Item = ErrorKind, Error = Error>> = Box::new(cancel_rx
+            .or_else(|_| future::empty())
+            .map(|_| ErrorKind::CommandCancelled));
+        if let Some((t, timeout)) = timeout {
+            stop = Box::new(stop.select(timeout
+                    .map(move |_| ErrorKind::CommandTimedOut(t))
+                    .map_err(|e| Error::with_chain(e, "Command timeout failed")))
+                .map(|(reason, _)| reason)
+                .map_err(|(e, _)| e));
+        }
+
+        let output: Box<Stream<Item = Chunk, Error = io::Error>> = match master {
+            Some(master) => {
+                let (reader, writer) = master.split();
+                if let Some(body) = stdin {
+                    write_stdin(handle, body, writer);
+                }
+                Box::new(PtyOutput::new(reader).map(Chunk::Stdout))
+            },
+            None => {
+                if let Some(body) = stdin {
+                    write_stdin(handle, body, child.stdin().take().unwrap());
+                }
+
+                let stdout = child.stdout().take().unwrap();
+                let outbuf = BufReader::new(stdout);
+                let stderr = child.stderr().take().unwrap();
+                let errbuf = BufReader::new(stderr);
+
+                Box::new(lines(outbuf).map(|l| Chunk::Stdout(l.into_bytes()))
+                    .select(lines(errbuf).map(|l| Chunk::Stderr(l.into_bytes()))))
+            },
+        };
+
         let (tx1, body) = Body::pair();
         let tx2 = tx1.clone();
 
-        let stdout = child.stdout().take().unwrap();
-        let outbuf = BufReader::new(stdout);
-        let stderr = child.stderr().take().unwrap();
-        let errbuf = BufReader::new(stderr);
+        let output = output
+            .map_err(|e| Error::with_chain(e, ErrorKind::Msg("Command execution failed".into())))
+            .and_then(|chunk| chunk.into_bytes().map(Ok))
+            .forward(tx1.sink_map_err(|e| Error::with_chain(e, "Could not forward command output to Body")));
+
+        // Wait for the command separately from its output, so that it's
+        // always reaped and deregistered, even if sending the output fails.
+        let registered = Registered { processes: processes.clone(), pid: pid };
+        let (status_tx, status_rx) = oneshot::channel();
+        handle.spawn(wait(handle, child, stop).then(move |r| {
+            drop(registered);
+            let _ = status_tx.send(r);
+            Ok(())
+        }));
+        let status = status_rx
+            .map_err(|_| Error::from("Command exited without a status"))
+            .and_then(future::result);
 
-        let status = child.map_err(|e| Error::with_chain(e, ErrorKind::Msg("Command execution failed".into())))
-            .and_then(|s| {
+        // The exit status is sent once all of the output has been, so that
+        // it's always the last chunk in the body.
+        let stream = output.join(status)
+            .and_then(|((_, tx1), s)| {
                 let status = ExitStatus {
                     success: s.success(),
                     code: s.code(),
+                    signal: s.signal().map(Signal::from_raw),
                 };
-                match serde_json::to_string(&status)
-                    .chain_err(|| "Could not serialize `ExitStatus` struct")
-                {
-                    Ok(s) => {
-                        let mut frame = "ExitStatus:".to_owned();
-                        frame.push_str(&s);
-                        Box::new(tx2.send(Ok(frame.into_bytes()))
-                            .map_err(|e| Error::with_chain(e, "Could not forward command output to Body"))
-                        ) as Box<Future<Item = mpsc::Sender<result::Result<Vec<u8>, io::Error>>, Error = Error>>
-                    },
-                    Err(e) => Box::new(future::err(e)),
-                }
-            });
-
-        let stream = lines(outbuf)
-            .select(lines(errbuf))
-            .map(|s| Ok(s.into_bytes()))
-            .map_err(|e| Error::with_chain(e, ErrorKind::Msg("Command execution failed".into())))
-            .forward(tx1.sink_map_err(|e| Error::with_chain(e, "Could not forward command output to Body")))
-            .join(status)
-            // @todo We should repatriate these errors somehow
+                future::result(Chunk::ExitStatus(status).into_bytes())
+                    .and_then(move |bytes| tx1.send(Ok(bytes)))
+            })
             .map(|_| ())
-            .map_err(|_| ());
+            // Send the error down the body so that the client knows why the
+            // output stopped, rather than just seeing the stream end. If it
+            // was the output that failed, there's no one left to read it, so
+            // stop the command too.
+            .or_else(move |e| {
+                processes.cancel(pid);
+                future::result(Chunk::Error(e.into()).into_bytes())
+                    .map_err(|_| ())
+                    .and_then(move |bytes| tx2.send(Ok(bytes))
+                        .map(|_| ())
+                        .map_err(|_| ()))
+            });
 
         handle.spawn(stream);
 
-        Box::new(future::ok(Message::WithBody(ResponseResult::Ok(Response::Null), body)))
+        Box::new(future::ok(Message::WithBody(ResponseResult::Ok(Response::Pid(pid)), body)))
+    }
+
+    fn cancel(&self, _: &Handle, pid: u32) -> ExecutableResult {
+        if Processes::current().cancel(pid) {
+            Box::new(future::ok(Message::WithoutBody(ResponseResult::Ok(Response::Null))))
+        } else {
+            Box::new(future::err(format!("No running command with PID {}", pid).into()))
+        }
+    }
+
+    fn signal(&self, _: &Handle, pid: u32, signal: Signal) -> ExecutableResult {
+        // Only signal our own commands, and only while they're running, so
+        // we never hit an unrelated process that has reused the PID.
+        if !Processes::current().0.borrow().contains_key(&pid) {
+            return Box::new(future::err(format!("No running command with PID {}", pid).into()));
+        }
+
+        if unsafe { libc::kill(-(pid as libc::pid_t), signal.to_raw()) } != 0 {
+            return Box::new(future::err(Error::with_chain(io::Error::last_os_error(), format!("Could not send {:?} to command", signal))));
+        }
+
+        Box::new(future::ok(Message::WithoutBody(ResponseResult::Ok(Response::Null))))
+    }
+
+    fn resize(&self, _: &Handle, pid: u32, size: WindowSize) -> ExecutableResult {
+        let processes = Processes::current();
+        let running = processes.0.borrow();
+        let pty = match running.get(&pid) {
+            Some(&Running { pty: Some(ref pty), .. }) => pty,
+            Some(_) => return Box::new(future::err(format!("Command with PID {} has no PTY", pid).into())),
+            None => return Box::new(future::err(format!("No running command with PID {}", pid).into())),
+        };
+
+        if let Err(e) = set_window_size(pty.as_raw_fd(), &size) {
+            return Box::new(future::err(Error::with_chain(e, "Could not resize PTY")));
+        }
+
+        Box::new(future::ok(Message::WithoutBody(ResponseResult::Ok(Response::Null))))
+    }
+}
+
+impl PtyOutput {
+    fn new(master: ReadHalf<Pty>) -> PtyOutput {
+        PtyOutput {
+            master: master,
+            buf: Vec::new(),
+            done: false,
+        }
+    }
+}
+
+impl Stream for PtyOutput {
+    type Item = Vec<u8>;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        let mut chunk = [0; 4096];
+
+        loop {
+            if self.done {
+                return Ok(Async::Ready(if self.buf.is_empty() {
+                    None
+                } else {
+                    Some(mem::take(&mut self.buf))
+                }));
+            }
+
+            match self.master.read(&mut chunk) {
+                Ok(0) => self.done = true,
+                Ok(n) => {
+                    self.buf.extend_from_slice(&chunk[..n]);
+                    let boundary = char_boundary(&self.buf);
+                    if boundary > 0 {
+                        let rest = self.buf.split_off(boundary);
+                        return Ok(Async::Ready(Some(mem::replace(&mut self.buf, rest))));
+                    }
+                },
+                // Linux returns EIO once the command has closed the PTY
+                Err(ref e) if e.raw_os_error() == Some(libc::EIO) => self.done = true,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(Async::NotReady),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+// Find the end of the last complete UTF-8 character in `buf`. Anything that
+// isn't UTF-8 at all is left for the client to deal with.
+fn char_boundary(buf: &[u8]) -> usize {
+    match str::from_utf8(buf) {
+        Ok(_) => buf.len(),
+        Err(e) => match e.error_len() {
+            None => e.valid_up_to(),
+            Some(_) => buf.len(),
+        },
+    }
+}
+
+// Write the request body to the command's stdin, then drop it to close it.
+fn write_stdin<W: AsyncWrite + 'static>(handle: &Handle, body: Body<Vec<u8>, io::Error>, stdin: W) {
+    handle.spawn(body.fold(stdin, |stdin, chunk| write_all(stdin, chunk).map(|(stdin, _)| stdin))
+        .map(|_| ())
+        // The command may exit without reading all of its input, which
+        // isn't our problem.
+        .map_err(|e| debug!("Could not write command stdin: {}", e)));
+}
+
+// Open a PTY and attach the command's stdio to its slave side, returning
+// the master side.
+fn attach_pty(command: &mut Command, size: &WindowSize) -> Result<File> {
+    let (master, slave) = open_pty(size)?;
+
+    command.stdin(slave.try_clone().chain_err(|| "Could not open PTY")?)
+        .stdout(slave.try_clone().chain_err(|| "Could not open PTY")?)
+        .stderr(slave);
+
+    // Start a new session with the PTY as its controlling terminal. This
+    // also puts the command in its own process group.
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+
+    Ok(master)
+}
+
+// Open a new PTY with the given window size, returning its master and
+// slave sides.
+fn open_pty(size: &WindowSize) -> Result<(File, File)> {
+    let master = unsafe { libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY) };
+    if master < 0 {
+        return Err(Error::with_chain(io::Error::last_os_error(), "Could not open PTY"));
+    }
+    let master = unsafe { File::from_raw_fd(master) };
+
+    let path = unsafe {
+        if libc::grantpt(master.as_raw_fd()) != 0 || libc::unlockpt(master.as_raw_fd()) != 0 {
+            return Err(Error::with_chain(io::Error::last_os_error(), "Could not open PTY"));
+        }
+        let name = libc::ptsname(master.as_raw_fd());
+        if name.is_null() {
+            return Err(Error::with_chain(io::Error::last_os_error(), "Could not open PTY"));
+        }
+        CStr::from_ptr(name).to_string_lossy().into_owned()
+    };
+
+    let slave = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY)
+        .open(&path)
+        .chain_err(|| "Could not open PTY")?;
+
+    set_window_size(master.as_raw_fd(), size).chain_err(|| "Could not open PTY")?;
+
+    Ok((master, slave))
+}
+
+fn set_window_size(fd: RawFd, size: &WindowSize) -> io::Result<()> {
+    let ws = libc::winsize {
+        ws_row: size.rows,
+        ws_col: size.cols,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    if unsafe { libc::ioctl(fd, libc::TIOCSWINSZ, &ws) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// Wait for `child` to exit, terminating it if `stop` resolves first.
+fn wait(handle: &Handle, child: Child, stop: Box<Future<Item = ErrorKind, Error = Error>>) -> Box<Future<Item = process::ExitStatus, Error = Error>> {
+    let handle = handle.clone();
+
+    Box::new(child.select2(stop).then(move |r| -> Box<Future<Item = _, Error = _>> {
+        match r {
+            Ok(Either::A((status, _))) => Box::new(future::ok(status)),
+            Err(Either::A((e, _))) => Box::new(future::err(Error::with_chain(e, ErrorKind::Msg("Command execution failed".into())))),
+            Ok(Either::B((reason, child))) => Box::new(terminate(&handle, child).then(|_| Err(reason.into()))),
+            Err(Either::B((e, _))) => Box::new(future::err(e)),
+        }
+    }))
+}
+
+// Send SIGTERM to the command's process group, then SIGKILL if the command
+// hasn't exited after the grace period.
+fn terminate(handle: &Handle, child: Child) -> Box<Future<Item = (), Error = ()>> {
+    let pgid = child.id() as libc::pid_t;
+    unsafe {
+        libc::kill(-pgid, libc::SIGTERM);
+    }
+
+    let grace = match Timeout::new(Duration::from_secs(KILL_GRACE_PERIOD), handle) {
+        Ok(t) => t,
+        Err(_) => {
+            unsafe {
+                libc::kill(-pgid, libc::SIGKILL);
+            }
+            return Box::new(child.then(|_| Ok(())));
+        },
+    };
+
+    Box::new(child.select2(grace).then(move |r| -> Box<Future<Item = _, Error = _>> {
+        match r {
+            Ok(Either::A(_)) | Err(Either::A(_)) => Box::new(future::ok(())),
+            Ok(Either::B((_, child))) | Err(Either::B((_, child))) => {
+                unsafe {
+                    libc::kill(-pgid, libc::SIGKILL);
+                }
+                Box::new(child.then(|_| Ok(())))
+            },
+        }
+    }))
+}
+
+fn identity(options: &ExecOptions) -> Result<Identity> {
+    let user = match options.user {
+        Some(ref name) => Some(users::get_user_by_name(name)
+            .ok_or_else(|| Error::from(format!("Unknown user \"{}\"", name)))?),
+        None => None,
+    };
+    let gid = match options.group {
+        Some(ref name) => Some(users::get_group_by_name(name)
+            .ok_or_else(|| Error::from(format!("Unknown group \"{}\"", name)))?
+            .gid()),
+        None => user.as_ref().map(|u| u.primary_group_id()),
+    };
+
+    let euid = get_effective_uid();
+    let uid = user.as_ref().map(|u| u.uid()).unwrap_or(euid);
+    let gid = gid.unwrap_or_else(get_effective_gid);
+
+    if uid == euid && gid == get_effective_gid() && (euid == 0 || options.escalate.is_none()) {
+        Ok(Identity::Current)
+    } else if euid == 0 {
+        Ok(Identity::Switch { uid, gid, user })
+    } else {
+        let mut prefix: Vec<String> = Vec::new();
+        match options.escalate {
+            Some(Escalation::Sudo) => {
+                prefix.extend(vec!["sudo".into(), "-n".into()]);
+                if let Some(ref u) = options.user {
+                    prefix.extend(vec!["-u".into(), u.clone()]);
+                }
+                if let Some(ref g) = options.group {
+                    prefix.extend(vec!["-g".into(), g.clone()]);
+                }
+            },
+            Some(Escalation::Doas) => {
+                if options.group.is_some() {
+                    return Err("doas cannot run commands as a group".into());
+                }
+                prefix.extend(vec!["doas".into(), "-n".into()]);
+                if let Some(ref u) = options.user {
+                    prefix.extend(vec!["-u".into(), u.clone()]);
+                }
+            },
+            None => return Err("Only root can run commands as another user. Try escalating with sudo or doas.".into()),
+        }
+        prefix.push("--".into());
+        Ok(Identity::Escalate(prefix))
+    }
+}
+
+// Get the supplementary groups for `user`, including `gid`.
+fn group_list(user: &str, gid: u32) -> Result<Vec<libc::gid_t>> {
+    let name = CString::new(user).chain_err(|| "Invalid user name")?;
+    let mut len: libc::c_int = 32;
+
+    loop {
+        let mut groups: Vec<libc::gid_t> = vec![0; len as usize];
+        let mut n = len;
+        let r = unsafe { libc::getgrouplist(name.as_ptr(), gid as _, groups.as_mut_ptr() as *mut _, &mut n) };
+        if r >= 0 {
+            groups.truncate(n as usize);
+            return Ok(groups);
+        }
+        // Some platforms tell us how big the list is, others don't
+        len = cmp::max(n, len * 2);
     }
 }
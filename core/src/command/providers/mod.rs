@@ -8,11 +8,13 @@
 
 mod generic;
 
-use command::ExitStatus;
+use command::{Chunk, Escalation, ExecOptions, ExitStatus, Signal, WindowSize};
 use errors::*;
 use remote::ExecutableResult;
-pub use self::generic::Generic;
+pub use self::generic::{Generic, Processes};
+use std::io;
 use tokio_core::reactor::Handle;
+use tokio_proto::streaming::Body;
 
 /// Specific implementation of `Command`
 #[derive(Clone, Copy, Serialize, Deserialize)]
@@ -22,7 +24,18 @@ pub enum Provider {
 
 pub trait CommandProvider {
     fn available() -> bool where Self: Sized;
-    fn exec(&self, &Handle, &[&str]) -> ExecutableResult;
+    fn exec(&self, handle: &Handle, cmd: &[&str]) -> ExecutableResult {
+        self.exec_with(handle, cmd, &ExecOptions::default(), None)
+    }
+    /// Execute a command with the given options, writing `stdin` (if any) to
+    /// the command's stdin.
+    fn exec_with(&self, &Handle, &[&str], &ExecOptions, Option<Body<Vec<u8>, io::Error>>) -> ExecutableResult;
+    /// Cancel the running command with the given PID.
+    fn cancel(&self, &Handle, u32) -> ExecutableResult;
+    /// Send a signal to the running command with the given PID.
+    fn signal(&self, &Handle, u32, Signal) -> ExecutableResult;
+    /// Resize the PTY of the running command with the given PID.
+    fn resize(&self, &Handle, u32, WindowSize) -> ExecutableResult;
 }
 
 #[doc(hidden)]
@@ -30,6 +43,6 @@ pub fn factory() -> Result<Box<CommandProvider>> {
     if Generic::available() {
         Ok(Box::new(Generic))
     } else {
-        Err(ErrorKind::ProviderUnavailable("Command").into())
+        Err(ErrorKind::ProviderUnavailable("Command".into()).into())
     }
 }
@@ -6,9 +6,13 @@
 
 //! The API error type.
 
+#[doc(hidden)] pub mod serializable;
+
 use futures::Future;
 use regex;
 use std::{error, io};
+use std::path::PathBuf;
+use std::time::Duration;
 
 error_chain! {
     foreign_links {
@@ -17,27 +21,55 @@ error_chain! {
     }
 
     errors {
-        Command(out: String) {
+        Command {
+            stdout: String,
+            stderr: String,
+            stdout_file: Option<PathBuf>,
+            stderr_file: Option<PathBuf>,
+        } {
             description("Command returned non-zero exit code"),
-            display("Command returned non-zero exit code with output: {}", out),
+            display("Command returned non-zero exit code with stdout: {}, stderr: {}", stdout, stderr),
+        }
+
+        CommandCancelled {
+            description("Command was cancelled"),
+            display("Command was cancelled"),
+        }
+
+        CommandTimedOut(after: Duration) {
+            description("Command timed out"),
+            display("Command timed out after {:?}", after),
+        }
+
+        Forbidden(reason: String) {
+            description("Request denied by host"),
+            display("Request denied by host: {}", reason),
+        }
+
+        IncompatibleProtocol {
+            local: u32,
+            remote: u32,
+        } {
+            description("Host speaks an incompatible protocol version"),
+            display("Host speaks protocol version {}, which is not compatible with our version {}", remote, local),
         }
 
         InvalidTelemetryKey {
-            cmd: &'static str,
+            cmd: String,
             key: String,
         } {
             description("Provided key not found in output"),
             display("Provided key '{}' not found in {} output", key, cmd),
         }
 
-        ProviderUnavailable(p: &'static str) {
+        ProviderUnavailable(p: String) {
             description("No providers available"),
             display("No providers available for {}", p),
         }
 
         Request {
-            endpoint: &'static str,
-            func: &'static str,
+            endpoint: String,
+            func: String,
         } {
             description("Could not run provider function on host"),
             display("Could not run {}::{}() on host", endpoint, func),
@@ -48,25 +80,35 @@ error_chain! {
             display("Error running command on remote host: {}", e),
         }
 
-        SystemCommand(c: &'static str) {
+        Unsupported(capability: String) {
+            description("Host does not support this request"),
+            display("Host does not support {}. Try upgrading its agent.", capability),
+        }
+
+        SystemCommand(c: String) {
             description("Error running system command"),
             display("Error running system command '{}'", c),
         }
 
-        SystemCommandOutput(c: &'static str) {
+        SystemCommandOutput(c: String) {
             description("Could not understand output of system command"),
             display("Could not understand output of system command '{}'", c),
         }
 
-        SystemFile(c: &'static str) {
+        SystemFile(c: String) {
             description("Could not open system file"),
             display("Could not open system file '{}'", c),
         }
 
-        SystemFileOutput(c: &'static str) {
+        SystemFileOutput(c: String) {
             description("Could not understand output of system file"),
             display("Could not understand output of system file '{}'", c),
         }
+
+        Unauthorized(reason: String) {
+            description("Authentication with host failed"),
+            display("Authentication with host failed: {}", reason),
+        }
     }
 }
 
@@ -0,0 +1,308 @@
+// Copyright 2015-2017 Intecture Developers.
+//
+// Licensed under the Mozilla Public License 2.0 <LICENSE or
+// https://www.tldrlegal.com/l/mpl-2.0>. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! A serializable replica of the API error type, which lets errors cross
+//! the wire intact. Errors that don't belong to this crate can't be rebuilt
+//! on the other side, so they are flattened into messages.
+
+use regex;
+use std::{error, fmt, io};
+use std::convert::From;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Error {
+    kind: ErrorKind,
+    cause: Option<Box<Error>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum ErrorKind {
+    Msg(String),
+    Io { kind: IoErrorKind, message: String },
+    Regex(String),
+    Command { stdout: String, stderr: String, stdout_file: Option<PathBuf>, stderr_file: Option<PathBuf> },
+    CommandCancelled,
+    CommandTimedOut(Duration),
+    Forbidden(String),
+    IncompatibleProtocol { local: u32, remote: u32 },
+    InvalidTelemetryKey { cmd: String, key: String },
+    ProviderUnavailable(String),
+    Request { endpoint: String, func: String },
+    Remote(String),
+    Unsupported(String),
+    SystemCommand(String),
+    SystemCommandOutput(String),
+    SystemFile(String),
+    SystemFileOutput(String),
+    Unauthorized(String),
+}
+
+/// Mirrors `io::ErrorKind`, which isn't serializable. Kinds that aren't
+/// listed here arrive as `Other`.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum IoErrorKind {
+    NotFound,
+    PermissionDenied,
+    ConnectionRefused,
+    ConnectionReset,
+    ConnectionAborted,
+    NotConnected,
+    AddrInUse,
+    AddrNotAvailable,
+    BrokenPipe,
+    AlreadyExists,
+    WouldBlock,
+    InvalidInput,
+    InvalidData,
+    TimedOut,
+    WriteZero,
+    Interrupted,
+    UnexpectedEof,
+    Other,
+}
+
+impl Error {
+    // Errors from other crates can't be rebuilt, so keep their messages
+    // and move on down the chain.
+    fn foreign(e: &error::Error) -> Error {
+        Error {
+            kind: ErrorKind::Msg(e.to_string()),
+            cause: e.source().map(|c| Box::new(Error::foreign(c))),
+        }
+    }
+}
+
+impl From<&super::Error> for Error {
+    fn from(e: &super::Error) -> Error {
+        Error {
+            kind: e.kind().into(),
+            cause: e.1.next_error.as_ref().map(|c| Box::new(if let Some(e) = c.downcast_ref::<super::Error>() {
+                e.into()
+            } else if let Some(e) = c.downcast_ref::<io::Error>() {
+                Error { kind: e.into(), cause: None }
+            } else {
+                Error::foreign(&**c)
+            })),
+        }
+    }
+}
+
+impl From<super::Error> for Error {
+    fn from(e: super::Error) -> Error {
+        Error::from(&e)
+    }
+}
+
+impl From<super::ErrorKind> for Error {
+    fn from(k: super::ErrorKind) -> Error {
+        Error {
+            kind: (&k).into(),
+            cause: None,
+        }
+    }
+}
+
+impl From<String> for Error {
+    fn from(s: String) -> Error {
+        Error {
+            kind: ErrorKind::Msg(s),
+            cause: None,
+        }
+    }
+}
+
+impl From<&str> for Error {
+    fn from(s: &str) -> Error {
+        Error::from(s.to_owned())
+    }
+}
+
+// Errors that were wrapped in an `io::Error` to get them onto the wire come
+// back out intact.
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        if e.get_ref().map(|r| r.is::<Error>()).unwrap_or(false) {
+            *e.into_inner().unwrap().downcast::<Error>().unwrap()
+        } else {
+            Error {
+                kind: (&e).into(),
+                cause: None,
+            }
+        }
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> io::Error {
+        io::Error::other(e)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", super::ErrorKind::from(self.kind.clone()))
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        "Error from remote host"
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        self.cause.as_ref().map(|c| &**c as &error::Error)
+    }
+}
+
+impl From<Error> for super::Error {
+    fn from(e: Error) -> super::Error {
+        let kind: super::ErrorKind = e.kind.into();
+        match e.cause {
+            Some(cause) => super::Error::with_chain(super::Error::from(*cause), kind),
+            None => kind.into(),
+        }
+    }
+}
+
+impl From<&super::ErrorKind> for ErrorKind {
+    fn from(k: &super::ErrorKind) -> ErrorKind {
+        match *k {
+            super::ErrorKind::Msg(ref m) => ErrorKind::Msg(m.clone()),
+            super::ErrorKind::Io(ref e) => e.into(),
+            super::ErrorKind::Regex(ref e) => ErrorKind::Regex(e.to_string()),
+            super::ErrorKind::Command { ref stdout, ref stderr, ref stdout_file, ref stderr_file } => ErrorKind::Command {
+                stdout: stdout.clone(),
+                stderr: stderr.clone(),
+                stdout_file: stdout_file.clone(),
+                stderr_file: stderr_file.clone(),
+            },
+            super::ErrorKind::CommandCancelled => ErrorKind::CommandCancelled,
+            super::ErrorKind::CommandTimedOut(after) => ErrorKind::CommandTimedOut(after),
+            super::ErrorKind::Forbidden(ref r) => ErrorKind::Forbidden(r.clone()),
+            super::ErrorKind::IncompatibleProtocol { local, remote } => ErrorKind::IncompatibleProtocol { local, remote },
+            super::ErrorKind::InvalidTelemetryKey { ref cmd, ref key } => ErrorKind::InvalidTelemetryKey { cmd: cmd.clone(), key: key.clone() },
+            super::ErrorKind::ProviderUnavailable(ref p) => ErrorKind::ProviderUnavailable(p.clone()),
+            super::ErrorKind::Request { ref endpoint, ref func } => ErrorKind::Request { endpoint: endpoint.clone(), func: func.clone() },
+            super::ErrorKind::Remote(ref e) => ErrorKind::Remote(e.clone()),
+            super::ErrorKind::Unsupported(ref c) => ErrorKind::Unsupported(c.clone()),
+            super::ErrorKind::SystemCommand(ref c) => ErrorKind::SystemCommand(c.clone()),
+            super::ErrorKind::SystemCommandOutput(ref c) => ErrorKind::SystemCommandOutput(c.clone()),
+            super::ErrorKind::SystemFile(ref c) => ErrorKind::SystemFile(c.clone()),
+            super::ErrorKind::SystemFileOutput(ref c) => ErrorKind::SystemFileOutput(c.clone()),
+            super::ErrorKind::Unauthorized(ref r) => ErrorKind::Unauthorized(r.clone()),
+            // Catches error_chain's hidden non-exhaustive variant
+            ref k => ErrorKind::Msg(k.to_string()),
+        }
+    }
+}
+
+impl From<ErrorKind> for super::ErrorKind {
+    fn from(k: ErrorKind) -> super::ErrorKind {
+        match k {
+            ErrorKind::Msg(m) => super::ErrorKind::Msg(m),
+            ErrorKind::Io { kind, message } => super::ErrorKind::Io(io::Error::new(kind.into(), message)),
+            ErrorKind::Regex(e) => super::ErrorKind::Regex(regex::Error::Syntax(e)),
+            ErrorKind::Command { stdout, stderr, stdout_file, stderr_file } => super::ErrorKind::Command { stdout, stderr, stdout_file, stderr_file },
+            ErrorKind::CommandCancelled => super::ErrorKind::CommandCancelled,
+            ErrorKind::CommandTimedOut(after) => super::ErrorKind::CommandTimedOut(after),
+            ErrorKind::Forbidden(r) => super::ErrorKind::Forbidden(r),
+            ErrorKind::IncompatibleProtocol { local, remote } => super::ErrorKind::IncompatibleProtocol { local, remote },
+            ErrorKind::InvalidTelemetryKey { cmd, key } => super::ErrorKind::InvalidTelemetryKey { cmd, key },
+            ErrorKind::ProviderUnavailable(p) => super::ErrorKind::ProviderUnavailable(p),
+            ErrorKind::Request { endpoint, func } => super::ErrorKind::Request { endpoint, func },
+            ErrorKind::Remote(e) => super::ErrorKind::Remote(e),
+            ErrorKind::Unsupported(c) => super::ErrorKind::Unsupported(c),
+            ErrorKind::SystemCommand(c) => super::ErrorKind::SystemCommand(c),
+            ErrorKind::SystemCommandOutput(c) => super::ErrorKind::SystemCommandOutput(c),
+            ErrorKind::SystemFile(c) => super::ErrorKind::SystemFile(c),
+            ErrorKind::SystemFileOutput(c) => super::ErrorKind::SystemFileOutput(c),
+            ErrorKind::Unauthorized(r) => super::ErrorKind::Unauthorized(r),
+        }
+    }
+}
+
+impl From<&io::Error> for ErrorKind {
+    fn from(e: &io::Error) -> ErrorKind {
+        ErrorKind::Io {
+            kind: e.kind().into(),
+            message: e.to_string(),
+        }
+    }
+}
+
+impl From<io::ErrorKind> for IoErrorKind {
+    fn from(k: io::ErrorKind) -> IoErrorKind {
+        match k {
+            io::ErrorKind::NotFound => IoErrorKind::NotFound,
+            io::ErrorKind::PermissionDenied => IoErrorKind::PermissionDenied,
+            io::ErrorKind::ConnectionRefused => IoErrorKind::ConnectionRefused,
+            io::ErrorKind::ConnectionReset => IoErrorKind::ConnectionReset,
+            io::ErrorKind::ConnectionAborted => IoErrorKind::ConnectionAborted,
+            io::ErrorKind::NotConnected => IoErrorKind::NotConnected,
+            io::ErrorKind::AddrInUse => IoErrorKind::AddrInUse,
+            io::ErrorKind::AddrNotAvailable => IoErrorKind::AddrNotAvailable,
+            io::ErrorKind::BrokenPipe => IoErrorKind::BrokenPipe,
+            io::ErrorKind::AlreadyExists => IoErrorKind::AlreadyExists,
+            io::ErrorKind::WouldBlock => IoErrorKind::WouldBlock,
+            io::ErrorKind::InvalidInput => IoErrorKind::InvalidInput,
+            io::ErrorKind::InvalidData => IoErrorKind::InvalidData,
+            io::ErrorKind::TimedOut => IoErrorKind::TimedOut,
+            io::ErrorKind::WriteZero => IoErrorKind::WriteZero,
+            io::ErrorKind::Interrupted => IoErrorKind::Interrupted,
+            io::ErrorKind::UnexpectedEof => IoErrorKind::UnexpectedEof,
+            _ => IoErrorKind::Other,
+        }
+    }
+}
+
+impl From<IoErrorKind> for io::ErrorKind {
+    fn from(k: IoErrorKind) -> io::ErrorKind {
+        match k {
+            IoErrorKind::NotFound => io::ErrorKind::NotFound,
+            IoErrorKind::PermissionDenied => io::ErrorKind::PermissionDenied,
+            IoErrorKind::ConnectionRefused => io::ErrorKind::ConnectionRefused,
+            IoErrorKind::ConnectionReset => io::ErrorKind::ConnectionReset,
+            IoErrorKind::ConnectionAborted => io::ErrorKind::ConnectionAborted,
+            IoErrorKind::NotConnected => io::ErrorKind::NotConnected,
+            IoErrorKind::AddrInUse => io::ErrorKind::AddrInUse,
+            IoErrorKind::AddrNotAvailable => io::ErrorKind::AddrNotAvailable,
+            IoErrorKind::BrokenPipe => io::ErrorKind::BrokenPipe,
+            IoErrorKind::AlreadyExists => io::ErrorKind::AlreadyExists,
+            IoErrorKind::WouldBlock => io::ErrorKind::WouldBlock,
+            IoErrorKind::InvalidInput => io::ErrorKind::InvalidInput,
+            IoErrorKind::InvalidData => io::ErrorKind::InvalidData,
+            IoErrorKind::TimedOut => io::ErrorKind::TimedOut,
+            IoErrorKind::WriteZero => io::ErrorKind::WriteZero,
+            IoErrorKind::Interrupted => io::ErrorKind::Interrupted,
+            IoErrorKind::UnexpectedEof => io::ErrorKind::UnexpectedEof,
+            IoErrorKind::Other => io::ErrorKind::Other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use errors;
+    use serde_json;
+    use std::io;
+    use super::Error;
+
+    #[test]
+    fn test_io_roundtrip() {
+        let e: errors::Error = io::Error::new(io::ErrorKind::PermissionDenied, "nope").into();
+        let json = serde_json::to_string(&Error::from(&e)).unwrap();
+        let e: errors::Error = serde_json::from_str::<Error>(&json).unwrap().into();
+        match *e.kind() {
+            errors::ErrorKind::Io(ref e) => {
+                assert_eq!(e.kind(), io::ErrorKind::PermissionDenied);
+                assert_eq!(e.to_string(), "nope");
+            },
+            ref k => panic!("Unexpected error {}", k),
+        }
+    }
+}
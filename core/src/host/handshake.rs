@@ -0,0 +1,341 @@
+// Copyright 2015-2017 Intecture Developers.
+//
+// Licensed under the Mozilla Public License 2.0 <LICENSE or
+// https://www.tldrlegal.com/l/mpl-2.0>. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! The handshake between a remote host and the agent.
+//!
+//! Before the first `Request` is sent, the agent greets each new connection
+//! with a `Hello`, which carries the range of protocol versions it speaks, its
+//! capabilities and the message encodings it accepts. If the
+//! agent has been configured with a pre-shared token, the `Hello` also carries
+//! a random challenge, which the client must sign with HMAC-SHA256 using the
+//! same token. The client replies with its own range of protocol versions and
+//! its chosen encoding, and only once the agent has welcomed the client do we
+//! hand the connection over to `WireProto`.
+//!
+//! Each side accepts the other so long as their ranges overlap. Beyond that,
+//! newer requests are gated by capabilities rather than by version. Peers that
+//! predate versioning don't send a version at all, so they are treated as
+//! version 0 and rejected, and peers that don't send a minimum version only
+//! speak their own version.
+//!
+//! Each side writes a single message and then waits for the other's reply,
+//! so neither side can read past the end of the handshake.
+
+use bytes::BytesMut;
+use errors::*;
+use futures::{future, Future, Sink, Stream};
+use openssl::hash::MessageDigest;
+use openssl::memcmp;
+use openssl::pkey::PKey;
+use openssl::rand::rand_bytes;
+use openssl::sign::Signer;
+use remote::{CAPABILITIES, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
+use serde_json;
+use std::io;
+use super::remote::Encoding;
+use tokio_io::{AsyncRead, AsyncWrite};
+use tokio_io::codec::{Decoder, Encoder};
+
+/// Length of the agent's challenge in bytes.
+const CHALLENGE_LEN: usize = 32;
+/// Longest handshake message we're prepared to buffer. The handshake happens
+/// before the client has authenticated, so this is kept small.
+const MAX_LINE_LEN: usize = 8 * 1024;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum Handshake {
+    Hello {
+        #[serde(default)]
+        version: u32,
+        #[serde(default)]
+        min_version: Option<u32>,
+        #[serde(default)]
+        capabilities: Vec<String>,
+        #[serde(default)]
+        encodings: Vec<Encoding>,
+        challenge: Option<String>,
+    },
+    Auth {
+        #[serde(default)]
+        version: u32,
+        #[serde(default)]
+        min_version: Option<u32>,
+        #[serde(default)]
+        encoding: Encoding,
+        response: Option<String>,
+    },
+    Welcome,
+    Rejected(String),
+}
+
+struct HandshakeCodec;
+
+/// Perform the client side of the handshake, returning the connection and
+/// the agent's capabilities once the agent has accepted us.
+pub fn client<T>(io: T, token: Option<String>, encoding: Encoding) -> Box<Future<Item = (T, Vec<String>), Error = Error>>
+    where T: AsyncRead + AsyncWrite + 'static
+{
+    Box::new(io.framed(HandshakeCodec)
+        .into_future()
+        .map_err(|(e, _)| Error::with_chain(e, "Could not read handshake from host"))
+        .and_then(move |(msg, framed)| {
+            let (version, min_version, capabilities, encodings, challenge) = match msg {
+                Some(Handshake::Hello { version, min_version, capabilities, encodings, challenge }) => (version, min_version, capabilities, encodings, challenge),
+                _ => return Box::new(future::err("Host sent an invalid handshake".into())) as Box<Future<Item = _, Error = Error>>,
+            };
+
+            if !compatible(version, min_version) {
+                return Box::new(future::err(ErrorKind::IncompatibleProtocol { local: PROTOCOL_VERSION, remote: version }.into()));
+            }
+
+            if !encodings.contains(&encoding) {
+                return Box::new(future::err(format!("Host does not accept {} encoding", encoding).into()));
+            }
+
+            let response = match challenge {
+                Some(challenge) => match token {
+                    Some(ref t) => match sign(t, &challenge) {
+                        Ok(r) => Some(r),
+                        Err(e) => return Box::new(future::err(e)),
+                    },
+                    None => return Box::new(future::err(ErrorKind::Unauthorized("Host requires a token".into()).into())),
+                },
+                None => None,
+            };
+
+            Box::new(framed.send(Handshake::Auth {
+                    version: PROTOCOL_VERSION,
+                    min_version: Some(MIN_PROTOCOL_VERSION),
+                    encoding,
+                    response,
+                })
+                .chain_err(|| "Could not send handshake to host")
+                .map(|framed| (framed, capabilities)))
+        })
+        .and_then(|(framed, capabilities)| {
+            framed.into_future()
+                .map_err(|(e, _)| Error::with_chain(e, "Could not read handshake from host"))
+                .map(|(msg, framed)| (msg, framed, capabilities))
+        })
+        .and_then(|(msg, framed, capabilities)| match msg {
+            Some(Handshake::Welcome) => Ok((framed.into_inner(), capabilities)),
+            Some(Handshake::Rejected(reason)) => Err(ErrorKind::Unauthorized(reason).into()),
+            _ => Err("Host sent an invalid handshake".into()),
+        }))
+}
+
+/// Perform the agent side of the handshake, returning the connection and the
+/// client's chosen encoding. If a token is provided, the client must prove
+/// that it knows it. The client may only choose one of the given `encodings`.
+///
+/// If the client is rejected, it is told why before this returns an error.
+pub fn server<T>(io: T, token: Option<String>, encodings: Vec<Encoding>) -> Box<Future<Item = (T, Encoding), Error = Error>>
+    where T: AsyncRead + AsyncWrite + 'static
+{
+    let challenge = match token {
+        Some(_) => match random_challenge() {
+            Ok(c) => Some(c),
+            Err(e) => return Box::new(future::err(e)),
+        },
+        None => None,
+    };
+
+    Box::new(io.framed(HandshakeCodec)
+        .send(Handshake::Hello {
+            version: PROTOCOL_VERSION,
+            min_version: Some(MIN_PROTOCOL_VERSION),
+            capabilities: CAPABILITIES.iter().map(|c| c.to_string()).collect(),
+            encodings: encodings.clone(),
+            challenge: challenge.clone(),
+        })
+        .chain_err(|| "Could not send handshake to client")
+        .and_then(|framed| {
+            framed.into_future()
+                .map_err(|(e, _)| Error::with_chain(e, "Could not read handshake from client"))
+        })
+        .and_then(move |(msg, framed)| {
+            let verdict = match msg {
+                Some(Handshake::Auth { version, min_version, .. }) if !compatible(version, min_version) =>
+                    Err(ErrorKind::IncompatibleProtocol { local: PROTOCOL_VERSION, remote: version }.into()),
+                Some(Handshake::Auth { encoding, .. }) if !encodings.contains(&encoding) =>
+                    Err(format!("Agent does not accept {} encoding", encoding).into()),
+                Some(Handshake::Auth { encoding, response, .. }) =>
+                    verify(token.as_ref(), challenge.as_ref(), response.as_ref()).map(|_| encoding),
+                _ => Err("Client sent an invalid handshake".into()),
+            };
+
+            match verdict {
+                Ok(encoding) => Box::new(framed.send(Handshake::Welcome)
+                    .chain_err(|| "Could not send handshake to client")
+                    .map(move |framed| (framed.into_inner(), encoding))) as Box<Future<Item = _, Error = Error>>,
+                Err(e) => {
+                    let reason = match *e.kind() {
+                        ErrorKind::Unauthorized(ref r) => r.clone(),
+                        ErrorKind::IncompatibleProtocol { local, remote } =>
+                            format!("Client speaks protocol version {}, which is not compatible with agent versions {} to {}", remote, MIN_PROTOCOL_VERSION, local),
+                        _ => e.to_string(),
+                    };
+                    Box::new(framed.send(Handshake::Rejected(reason))
+                        .chain_err(|| "Could not send handshake to client")
+                        .and_then(|_| future::err(e)))
+                },
+            }
+        }))
+}
+
+// Whether a peer that speaks versions `min_version` to `version` can talk to
+// us.
+fn compatible(version: u32, min_version: Option<u32>) -> bool {
+    let min_version = min_version.unwrap_or(version);
+    version >= MIN_PROTOCOL_VERSION && min_version <= PROTOCOL_VERSION
+}
+
+fn verify(token: Option<&String>, challenge: Option<&String>, response: Option<&String>) -> Result<()> {
+    match (token, challenge) {
+        (Some(token), Some(challenge)) => {
+            let expected = sign(token, challenge)?;
+            match response {
+                Some(r) if r.len() == expected.len() && memcmp::eq(r.as_bytes(), expected.as_bytes()) => Ok(()),
+                Some(_) => Err(ErrorKind::Unauthorized("Invalid token".into()).into()),
+                None => Err(ErrorKind::Unauthorized("Missing token".into()).into()),
+            }
+        },
+        _ => Ok(()),
+    }
+}
+
+fn sign(token: &str, challenge: &str) -> Result<String> {
+    let key = PKey::hmac(token.as_bytes()).chain_err(|| "Could not create HMAC key")?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key).chain_err(|| "Could not create HMAC signer")?;
+    signer.update(challenge.as_bytes()).chain_err(|| "Could not sign challenge")?;
+    let hmac = signer.sign_to_vec().chain_err(|| "Could not sign challenge")?;
+    Ok(to_hex(&hmac))
+}
+
+fn random_challenge() -> Result<String> {
+    let mut buf = [0; CHALLENGE_LEN];
+    rand_bytes(&mut buf).chain_err(|| "Could not generate challenge")?;
+    Ok(to_hex(&buf))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl Decoder for HandshakeCodec {
+    type Item = Handshake;
+    type Error = io::Error;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> io::Result<Option<Self::Item>> {
+        let line = match buf.iter().position(|b| *b == b'\n') {
+            Some(n) if n <= MAX_LINE_LEN => buf.split_to(n),
+            None if buf.len() <= MAX_LINE_LEN => return Ok(None),
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Handshake exceeds maximum length of {}", MAX_LINE_LEN))),
+        };
+
+        buf.split_to(1);
+
+        serde_json::from_slice(&line)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+impl Encoder for HandshakeCodec {
+    type Item = Handshake;
+    type Error = io::Error;
+
+    fn encode(&mut self, msg: Self::Item, buf: &mut BytesMut) -> io::Result<()> {
+        let json = serde_json::to_vec(&msg)
+            .map_err(io::Error::other)?;
+        buf.extend(&json);
+        buf.extend(b"\n");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use remote::{MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
+    use std::io;
+    use super::*;
+    use tokio_io::codec::{Decoder, Encoder};
+
+    #[test]
+    fn test_sign() {
+        // RFC 4231, test case 2
+        assert_eq!(sign("Jefe", "what do ya want for nothing?").unwrap(),
+                   "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+    }
+
+    #[test]
+    fn test_verify() {
+        let token = "s3cret".to_owned();
+        let challenge = random_challenge().unwrap();
+        let response = sign(&token, &challenge).unwrap();
+
+        assert!(verify(Some(&token), Some(&challenge), Some(&response)).is_ok());
+        assert!(verify(None, None, None).is_ok());
+
+        let wrong = sign("wrong", &challenge).unwrap();
+        match *verify(Some(&token), Some(&challenge), Some(&wrong)).unwrap_err().kind() {
+            ErrorKind::Unauthorized(ref r) => assert_eq!(r, "Invalid token"),
+            ref e => panic!("Unexpected error {}", e),
+        }
+        match *verify(Some(&token), Some(&challenge), None).unwrap_err().kind() {
+            ErrorKind::Unauthorized(ref r) => assert_eq!(r, "Missing token"),
+            ref e => panic!("Unexpected error {}", e),
+        }
+
+        let truncated = response[..10].to_owned();
+        assert!(verify(Some(&token), Some(&challenge), Some(&truncated)).is_err());
+    }
+
+    #[test]
+    fn test_compatible() {
+        assert!(compatible(PROTOCOL_VERSION, None));
+        assert!(compatible(PROTOCOL_VERSION + 1, Some(PROTOCOL_VERSION)));
+        assert!(!compatible(PROTOCOL_VERSION + 1, Some(PROTOCOL_VERSION + 1)));
+        assert!(!compatible(PROTOCOL_VERSION + 1, None));
+        assert!(!compatible(MIN_PROTOCOL_VERSION - 1, None));
+        assert!(!compatible(0, None));
+    }
+
+    #[test]
+    fn test_codec() {
+        let mut codec = HandshakeCodec;
+        let mut buf = BytesMut::new();
+        codec.encode(Handshake::Rejected("nope".into()), &mut buf).unwrap();
+        codec.encode(Handshake::Welcome, &mut buf).unwrap();
+
+        match codec.decode(&mut buf).unwrap() {
+            Some(Handshake::Rejected(ref r)) if r == "nope" => (),
+            m => panic!("Unexpected message {:?}", m),
+        }
+        match codec.decode(&mut buf).unwrap() {
+            Some(Handshake::Welcome) => (),
+            m => panic!("Unexpected message {:?}", m),
+        }
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_max_line_len() {
+        let mut codec = HandshakeCodec;
+
+        // Without a newline, we'd otherwise buffer forever
+        let mut buf = BytesMut::from(vec![b'a'; MAX_LINE_LEN]);
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+        buf.extend(b"a");
+        assert_eq!(codec.decode(&mut buf).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        let mut line = vec![b'a'; MAX_LINE_LEN + 1];
+        line.push(b'\n');
+        let mut buf = BytesMut::from(line);
+        assert_eq!(codec.decode(&mut buf).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -6,6 +6,7 @@
 
 //! A connection to the local machine.
 
+use command::Processes;
 use errors::*;
 use futures::{future, Future};
 use remote::{Executable, Request, Response, ResponseResult};
@@ -22,6 +23,7 @@ use tokio_proto::streaming::{Body, Message};
 pub struct Local {
     inner: Arc<Inner>,
     handle: Handle,
+    processes: Processes,
 }
 
 struct Inner {
@@ -34,6 +36,7 @@ impl Local {
         let mut host = Local {
             inner: Arc::new(Inner { telemetry: None }),
             handle: handle.clone(),
+            processes: Processes::default(),
         };
 
         Box::new(telemetry::Telemetry::load(&host)
@@ -43,6 +46,22 @@ impl Local {
                 host
             }))
     }
+
+    /// Start a new session with this host. Clones of a `Local` share the
+    /// commands they've started, whereas sessions only see their own.
+    #[doc(hidden)]
+    pub fn session(&self) -> Local {
+        Local {
+            inner: self.inner.clone(),
+            handle: self.handle.clone(),
+            processes: Processes::default(),
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn processes(&self) -> &Processes {
+        &self.processes
+    }
 }
 
 impl Host for Local {
@@ -54,12 +73,16 @@ impl Host for Local {
         &self.handle
     }
 
+    // We are the agent, so we support everything we know about.
+    fn supports(&self, _: &str) -> bool {
+        true
+    }
+
     #[doc(hidden)]
     fn request_msg(&self, msg: Message<Request, Body<Vec<u8>, io::Error>>) ->
         Box<Future<Item = Message<Response, Body<Vec<u8>, io::Error>>, Error = Error>>
     {
-        Box::new(msg.into_inner()
-           .exec(self)
+        Box::new(msg.exec(self)
            .and_then(|mut msg| {
                let body = msg.take_body();
                match msg.into_inner() {
@@ -69,6 +92,7 @@ impl Host for Local {
                        future::ok(Message::WithoutBody(response))
                    },
                    ResponseResult::Err(e) => future::err(e.into()),
+                   ResponseResult::Denied(e) => future::err(ErrorKind::Forbidden(e).into()),
                }
            }))
     }
@@ -6,6 +6,7 @@
 
 //! Manages the connection between the API and a server.
 
+#[doc(hidden)] pub mod handshake;
 pub mod local;
 pub mod remote;
 
@@ -23,6 +24,10 @@ pub trait Host: Clone {
     fn telemetry(&self) -> &Telemetry;
     /// Get `Handle` to Tokio reactor.
     fn handle(&self) -> &Handle;
+    /// Check whether this host supports a capability, such as the name of a
+    /// `Request`. Endpoints should check this before sending requests that
+    /// older agents may not understand.
+    fn supports(&self, capability: &str) -> bool;
     #[doc(hidden)]
     fn request(&self, request: Request) ->
         Box<Future<Item = Message<Response, Body<Vec<u8>, io::Error>>, Error = Error>>
@@ -6,74 +6,205 @@
 
 //! A connection to a remote host.
 
-use bytes::BytesMut;
+use bytes::{Buf, BufMut, BytesMut};
 use errors::*;
-use futures::{future, Future};
+use errors::serializable;
+use futures::{future, Future, Poll};
+use openssl::ssl::{SslConnector, SslFiletype, SslMethod};
 use remote::{Request, Response, ResponseResult};
+use serde_cbor;
 use serde_json;
-use std::{io, result};
+use std::{fmt, io, mem, process, result};
+use std::io::{Read, Write};
+use std::marker::PhantomData;
 use std::net::SocketAddr;
+use std::path::Path;
 use std::sync::Arc;
-use super::Host;
+use super::{handshake, Host};
 use telemetry::{self, Telemetry};
+use tokio_core::net::TcpStream;
 use tokio_core::reactor::Handle;
 use tokio_io::{AsyncRead, AsyncWrite};
 use tokio_io::codec::{Encoder, Decoder, Framed};
+use tokio_openssl::SslConnectorExt;
+use tokio_process::{Child, ChildStdin, ChildStdout, CommandExt};
+use tokio_proto::BindClient;
 use tokio_proto::streaming::{Body, Message};
 use tokio_proto::streaming::pipeline::{ClientProto, Frame, ServerProto};
-use tokio_proto::TcpClient;
 use tokio_proto::util::client_proxy::ClientProxy;
 use tokio_service::Service;
+use tokio_uds::UnixStream;
+
+/// The command used to start the agent on the far end of an `Ssh` host.
+const REMOTE_AGENT: [&'static str; 2] = ["intecture_agent", "--stdio"];
+
+/// Length of each frame's header, which is a one byte frame type followed by
+/// the payload length as a big endian `u32`.
+const FRAME_HEADER_LEN: usize = 5;
+/// Largest frame payload we're prepared to buffer.
+const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+/// Largest body chunk we're prepared to join back together from parts.
+const MAX_CHUNK_LEN: usize = 16 * MAX_FRAME_LEN;
+
+const FRAME_MESSAGE: u8 = 0;
+const FRAME_MESSAGE_WITH_BODY: u8 = 1;
+const FRAME_BODY_CHUNK: u8 = 2;
+const FRAME_BODY_END: u8 = 3;
+const FRAME_ERROR: u8 = 4;
+// A body chunk that's too big for one frame is sent as a run of parts,
+// followed by a `FRAME_BODY_CHUNK` with the last of it.
+const FRAME_BODY_PART: u8 = 5;
 
 #[doc(hidden)]
-pub type LineMessage = Message<serde_json::Value, Body<Vec<u8>, io::Error>>;
+pub type WireMessage = Message<serde_json::Value, Body<Vec<u8>, io::Error>>;
 
-/// A `Host` type that uses an unencrypted socket.
+/// A connection to a remote host over transport `T`.
 ///
-/// >**Warning!** An unencrypted host is susceptible to eavesdropping and MITM
-/// attacks, and should only be used on secure private networks.
-#[derive(Clone)]
-pub struct Plain {
+/// You won't normally name this type yourself. Instead, use one of the
+/// aliases for each transport, e.g. [`Plain`](type.Plain.html) or
+/// [`Ssh`](type.Ssh.html).
+pub struct RemoteHost<T> {
     inner: Arc<Inner>,
     handle: Handle,
+    _transport: PhantomData<T>,
 }
 
+/// A `Host` type that uses an unencrypted socket.
+///
+/// >**Warning!** An unencrypted host is susceptible to eavesdropping and MITM
+/// >attacks, and should only be used on secure private networks.
+pub type Plain = RemoteHost<PlainTransport>;
+
+/// A `Host` type that encrypts its connection using TLS.
+///
+/// The agent's certificate is verified against the CA certificate you
+/// provide, and the connection is refused if verification fails. If the agent
+/// requires client certificates, use
+/// [`Tls::connect_with_identity()`](struct.RemoteHost.html#method.connect_with_identity)
+/// to present your own certificate and private key.
+pub type Tls = RemoteHost<TlsTransport>;
+
+/// A `Host` type that talks to an agent running in a child process.
+///
+/// The process is spawned on the local machine, and should run
+/// `intecture_agent --stdio` either directly or via some wrapper that gives
+/// you a shell, e.g. `sudo`, `docker exec` or `ssh`. Requests are sent to
+/// the agent through the process' stdin and stdout, so no network listener
+/// is required.
+///
+///## Example
+///
+/// Manage a Docker container:
+///
+///```no_run
+///extern crate intecture_api;
+///extern crate tokio_core;
+///
+///use intecture_api::prelude::*;
+///use tokio_core::reactor::Core;
+///
+///# fn main() {
+///let mut core = Core::new().unwrap();
+///let handle = core.handle();
+///
+///let host = Process::spawn(&["docker", "exec", "-i", "my_container", "intecture_agent", "--stdio"], &handle);
+///core.run(host).unwrap();
+///# }
+///```
+pub type Process = RemoteHost<ProcessTransport>;
+
+/// A `Host` type that tunnels its connection through SSH.
+///
+/// Rather than connecting to a listening agent, `Ssh` logs in to the host
+/// using your system's `ssh` client and starts `intecture_agent --stdio` on
+/// the far end. Authentication is handled entirely by SSH, so your keys,
+/// `ssh-agent` and `~/.ssh/config` all work as usual. The agent binary must
+/// be installed in the remote user's `PATH`.
+///
+/// >**Note:** `ssh` is run in batch mode, so it will not prompt for
+/// >passwords. Use key based authentication instead.
+///
+/// This is a convenience wrapper around [`Process`](type.Process.html).
+pub type Ssh = RemoteHost<SshTransport>;
+
+/// A `Host` type that connects to an agent on the local machine via a Unix
+/// domain socket.
+///
+/// This is useful for privilege separation, where the agent runs as root and
+/// your project runs as an ordinary user. Access to the agent is controlled
+/// by the permissions on the socket file, so make sure your user can read and
+/// write to it.
+pub type Unix = RemoteHost<UnixTransport>;
+
+/// Transport for [`Plain`](type.Plain.html) hosts.
+pub enum PlainTransport {}
+/// Transport for [`Tls`](type.Tls.html) hosts.
+pub enum TlsTransport {}
+/// Transport for [`Process`](type.Process.html) hosts.
+pub enum ProcessTransport {}
+/// Transport for [`Ssh`](type.Ssh.html) hosts.
+pub enum SshTransport {}
+/// Transport for [`Unix`](type.Unix.html) hosts.
+pub enum UnixTransport {}
+
 struct Inner {
-    inner: ClientProxy<LineMessage, LineMessage, io::Error>,
+    inner: ClientProxy<WireMessage, WireMessage, io::Error>,
+    capabilities: Vec<String>,
     telemetry: Option<Telemetry>,
 }
 
+// Joins a child process' stdout and stdin into a single duplex stream. The
+// child is killed when this is dropped.
+struct ChildIo {
+    _child: Child,
+    stdin: ChildStdin,
+    stdout: ChildStdout,
+}
+
+/// The encoding used for message headers on the wire. Message bodies are
+/// always sent as raw bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    /// Compact binary encoding
+    Cbor,
+    /// Human readable encoding, which is handy for debugging
+    #[default]
+    Json,
+}
+
 #[doc(hidden)]
-pub struct JsonLineCodec {
+pub struct WireCodec {
+    encoding: Encoding,
     decoding_head: bool,
+    // The parts of a body chunk that we've decoded so far
+    partial: Vec<u8>,
 }
 #[doc(hidden)]
-pub struct JsonLineProto;
+pub struct WireProto {
+    encoding: Encoding,
+}
 
-impl Plain {
-    /// Create a new Host connected to the given address.
-    pub fn connect(addr: &str, handle: &Handle) -> Box<Future<Item = Plain, Error = Error>> {
-        let addr: SocketAddr = match addr.parse().chain_err(|| "Invalid host address") {
-            Ok(addr) => addr,
-            Err(e) => return Box::new(future::err(e)),
-        };
+impl<T: 'static> RemoteHost<T> {
+    // Shake hands with the agent over `io`, then load the host's telemetry.
+    fn bind<S>(io: S, token: Option<String>, encoding: Encoding, handle: &Handle) -> Box<Future<Item = RemoteHost<T>, Error = Error>>
+        where S: AsyncRead + AsyncWrite + 'static
+    {
         let handle = handle.clone();
 
-        info!("Connecting to host {}", addr);
-
-        Box::new(TcpClient::new(JsonLineProto)
-            .connect(&addr, &handle)
-            .chain_err(|| "Could not connect to host")
-            .and_then(move |client_service| {
+        Box::new(handshake::client(io, token, encoding)
+            .and_then(move |(io, capabilities)| {
                 info!("Connected!");
 
-                let mut host = Plain {
+                let mut host = RemoteHost {
                     inner: Arc::new(
                         Inner {
-                            inner: client_service,
+                            inner: WireProto::new(encoding).bind_client(&handle, io),
+                            capabilities: capabilities,
                             telemetry: None,
                         }),
-                    handle: handle.clone(),
+                    handle: handle,
+                    _transport: PhantomData,
                 };
 
                 telemetry::Telemetry::load(&host)
@@ -86,30 +217,211 @@ impl Plain {
     }
 }
 
-impl Host for Plain {
-    fn telemetry(&self) -> &Telemetry {
-        self.inner.telemetry.as_ref().unwrap()
+impl Plain {
+    /// Create a new Host connected to the given address.
+    pub fn connect(addr: &str, handle: &Handle) -> Box<Future<Item = Plain, Error = Error>> {
+        Self::do_connect(addr, None, Encoding::default(), handle)
     }
 
-    fn handle(&self) -> &Handle {
-        &self.handle
+    /// Create a new Host connected to the given address, authenticating
+    /// with the agent's pre-shared token.
+    pub fn connect_with_token(addr: &str, token: &str, handle: &Handle) -> Box<Future<Item = Plain, Error = Error>> {
+        Self::do_connect(addr, Some(token.into()), Encoding::default(), handle)
     }
 
-    #[doc(hidden)]
-    fn request_msg(&self, msg: Message<Request, Body<Vec<u8>, io::Error>>) ->
-        Box<Future<Item = Message<Response, Body<Vec<u8>, io::Error>>, Error = Error>>
-    {
-        self.call(msg)
+    /// Create a new Host connected to the given address, using `encoding`
+    /// for messages sent over the wire. If the agent requires a token, pass
+    /// it in `token`.
+    pub fn connect_with_encoding(addr: &str, token: Option<&str>, encoding: Encoding, handle: &Handle) -> Box<Future<Item = Plain, Error = Error>> {
+        Self::do_connect(addr, token.map(|t| t.into()), encoding, handle)
+    }
+
+    fn do_connect(addr: &str, token: Option<String>, encoding: Encoding, handle: &Handle) -> Box<Future<Item = Plain, Error = Error>> {
+        let addr: SocketAddr = match addr.parse().chain_err(|| "Invalid host address") {
+            Ok(addr) => addr,
+            Err(e) => return Box::new(future::err(e)),
+        };
+        let handle = handle.clone();
+
+        info!("Connecting to host {}", addr);
+
+        Box::new(TcpStream::connect(&addr, &handle)
+            .chain_err(|| "Could not connect to host")
+            .and_then(move |sock| Self::bind(sock, token, encoding, &handle)))
     }
 }
 
-impl Service for Plain {
-    type Request = Message<Request, Body<Vec<u8>, io::Error>>;
-    type Response = Message<Response, Body<Vec<u8>, io::Error>>;
-    type Error = Error;
-    type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
+impl Tls {
+    /// Create a new Host connected to the given address.
+    ///
+    /// The `domain` is the name that the agent's certificate was issued
+    /// for, and `ca` is the path to the PEM encoded CA certificate that
+    /// signed it.
+    pub fn connect<P: AsRef<Path>>(addr: &str, domain: &str, ca: P, handle: &Handle) -> Box<Future<Item = Tls, Error = Error>> {
+        Self::do_connect(addr, domain, ca.as_ref(), None, None, handle)
+    }
+
+    /// Create a new Host connected to the given address, authenticating
+    /// with the agent's pre-shared token. See
+    /// [`Tls::connect()`](#method.connect) for the remaining arguments.
+    pub fn connect_with_token<P: AsRef<Path>>(addr: &str, domain: &str, ca: P, token: &str, handle: &Handle) -> Box<Future<Item = Tls, Error = Error>> {
+        Self::do_connect(addr, domain, ca.as_ref(), None, Some(token.into()), handle)
+    }
+
+    /// Create a new Host connected to the given address, authenticating
+    /// ourselves with a client certificate.
+    ///
+    /// The `cert` and `key` are paths to the PEM encoded client certificate
+    /// and its private key. If the agent also requires a token, pass it in
+    /// `token`. See [`Tls::connect()`](#method.connect) for the remaining
+    /// arguments.
+    pub fn connect_with_identity<P: AsRef<Path>>(addr: &str, domain: &str, ca: P, cert: P, key: P, token: Option<&str>, handle: &Handle) -> Box<Future<Item = Tls, Error = Error>> {
+        Self::do_connect(addr, domain, ca.as_ref(), Some((cert.as_ref(), key.as_ref())), token.map(|t| t.into()), handle)
+    }
+
+    fn do_connect(addr: &str, domain: &str, ca: &Path, identity: Option<(&Path, &Path)>, token: Option<String>, handle: &Handle) -> Box<Future<Item = Tls, Error = Error>> {
+        let addr: SocketAddr = match addr.parse().chain_err(|| "Invalid host address") {
+            Ok(addr) => addr,
+            Err(e) => return Box::new(future::err(e)),
+        };
+        let connector = match tls_connector(ca, identity) {
+            Ok(c) => c,
+            Err(e) => return Box::new(future::err(e)),
+        };
+        let domain = domain.to_owned();
+        let handle = handle.clone();
+
+        info!("Connecting to host {}", addr);
+
+        Box::new(TcpStream::connect(&addr, &handle)
+            .chain_err(|| "Could not connect to host")
+            .and_then(move |sock| {
+                connector.connect_async(&domain, sock)
+                    .map_err(|e| Error::with_chain(e, "TLS handshake with host failed"))
+                    .and_then(move |stream| Self::bind(stream, token, Encoding::default(), &handle))
+            }))
+    }
+}
+
+impl Ssh {
+    /// Create a new Host connected to the given destination, e.g.
+    /// "user@example.com".
+    pub fn connect(dest: &str, handle: &Handle) -> Box<Future<Item = Ssh, Error = Error>> {
+        Self::connect_with_args(dest, &[], None, handle)
+    }
+
+    /// Create a new Host connected to the given destination, authenticating
+    /// with the agent's pre-shared token.
+    pub fn connect_with_token(dest: &str, token: &str, handle: &Handle) -> Box<Future<Item = Ssh, Error = Error>> {
+        Self::connect_with_args(dest, &[], Some(token), handle)
+    }
+
+    /// Create a new Host connected to the given destination, passing extra
+    /// arguments to `ssh`. If the agent requires a token, pass it in
+    /// `token`.
+    ///
+    ///## Example
+    ///
+    /// Connect to a nonstandard port using a specific private key:
+    ///
+    ///```no_run
+    ///extern crate intecture_api;
+    ///extern crate tokio_core;
+    ///
+    ///use intecture_api::prelude::*;
+    ///use tokio_core::reactor::Core;
+    ///
+    ///# fn main() {
+    ///let mut core = Core::new().unwrap();
+    ///let handle = core.handle();
+    ///
+    ///let host = Ssh::connect_with_args("user@example.com", &["-p", "2222", "-i", "/path/to/key"], None, &handle);
+    ///core.run(host).unwrap();
+    ///# }
+    ///```
+    pub fn connect_with_args(dest: &str, args: &[&str], token: Option<&str>, handle: &Handle) -> Box<Future<Item = Ssh, Error = Error>> {
+        let mut cmd = process::Command::new("ssh");
+        cmd.args(["-T", "-o", "BatchMode=yes"])
+           .args(args)
+           .arg(dest)
+           .args(REMOTE_AGENT);
+
+        info!("Connecting to host {} via SSH", dest);
+
+        Box::new(Process::spawn_command(&mut cmd, token.map(|t| t.into()), handle)
+            .map(|p| Ssh {
+                inner: p.inner,
+                handle: p.handle,
+                _transport: PhantomData,
+            }))
+    }
+}
+
+impl Process {
+    /// Create a new Host by spawning the given command. The first item of
+    /// `cmd` is the program to run, and the rest are its arguments.
+    pub fn spawn(cmd: &[&str], handle: &Handle) -> Box<Future<Item = Process, Error = Error>> {
+        Self::do_spawn(cmd, None, handle)
+    }
 
-    fn call(&self, mut req: Self::Request) -> Self::Future {
+    /// Create a new Host by spawning the given command, authenticating with
+    /// the agent's pre-shared token.
+    pub fn spawn_with_token(cmd: &[&str], token: &str, handle: &Handle) -> Box<Future<Item = Process, Error = Error>> {
+        Self::do_spawn(cmd, Some(token.into()), handle)
+    }
+
+    fn do_spawn(cmd: &[&str], token: Option<String>, handle: &Handle) -> Box<Future<Item = Process, Error = Error>> {
+        let (program, args) = match cmd.split_first() {
+            Some((p, a)) => (p, a),
+            None => return Box::new(future::err("Invalid command provided".into())),
+        };
+
+        let mut cmd = process::Command::new(program);
+        cmd.args(args);
+
+        info!("Spawning host process {}", program);
+
+        Self::spawn_command(&mut cmd, token, handle)
+    }
+
+    fn spawn_command(cmd: &mut process::Command, token: Option<String>, handle: &Handle) -> Box<Future<Item = Process, Error = Error>> {
+        let io = match ChildIo::spawn(cmd, handle).chain_err(|| "Could not spawn host process") {
+            Ok(io) => io,
+            Err(e) => return Box::new(future::err(e)),
+        };
+
+        Self::bind(io, token, Encoding::default(), handle)
+    }
+}
+
+impl Unix {
+    /// Create a new Host connected to the socket at the given path.
+    pub fn connect<P: AsRef<Path>>(path: P, handle: &Handle) -> Box<Future<Item = Unix, Error = Error>> {
+        Self::do_connect(path.as_ref(), None, handle)
+    }
+
+    /// Create a new Host connected to the socket at the given path,
+    /// authenticating with the agent's pre-shared token.
+    pub fn connect_with_token<P: AsRef<Path>>(path: P, token: &str, handle: &Handle) -> Box<Future<Item = Unix, Error = Error>> {
+        Self::do_connect(path.as_ref(), Some(token.into()), handle)
+    }
+
+    fn do_connect(path: &Path, token: Option<String>, handle: &Handle) -> Box<Future<Item = Unix, Error = Error>> {
+        info!("Connecting to socket {}", path.display());
+
+        let sock = match UnixStream::connect(path, handle).chain_err(|| "Could not connect to socket") {
+            Ok(s) => s,
+            Err(e) => return Box::new(future::err(e)),
+        };
+
+        Self::bind(sock, token, Encoding::default(), handle)
+    }
+}
+
+impl Inner {
+    fn call(&self, mut req: Message<Request, Body<Vec<u8>, io::Error>>) ->
+        Box<Future<Item = Message<Response, Body<Vec<u8>, io::Error>>, Error = Error>>
+    {
         let body = req.take_body();
         let request = req.into_inner();
 
@@ -125,7 +437,7 @@ impl Service for Plain {
             None => Message::WithoutBody(value),
         };
 
-        Box::new(self.inner.inner.call(json_msg)
+        Box::new(self.inner.call(json_msg)
             .chain_err(|| "Error while running provider on host")
             .and_then(|mut msg| {
                 let body = msg.take_body();
@@ -140,7 +452,8 @@ impl Service for Plain {
 
                 let msg = match result {
                     ResponseResult::Ok(msg) => msg,
-                    ResponseResult::Err(e) => return Box::new(future::err(ErrorKind::Remote(e).into())),
+                    ResponseResult::Err(e) => return Box::new(future::err(e.into())),
+                    ResponseResult::Denied(e) => return Box::new(future::err(ErrorKind::Forbidden(e).into())),
                 };
                 Box::new(future::ok(match body {
                     Some(b) => Message::WithBody(msg, b),
@@ -150,127 +463,439 @@ impl Service for Plain {
     }
 }
 
-impl Decoder for JsonLineCodec {
+impl<T> Clone for RemoteHost<T> {
+    fn clone(&self) -> RemoteHost<T> {
+        RemoteHost {
+            inner: self.inner.clone(),
+            handle: self.handle.clone(),
+            _transport: PhantomData,
+        }
+    }
+}
+
+impl<T> Host for RemoteHost<T> {
+    fn telemetry(&self) -> &Telemetry {
+        self.inner.telemetry.as_ref().unwrap()
+    }
+
+    fn handle(&self) -> &Handle {
+        &self.handle
+    }
+
+    fn supports(&self, capability: &str) -> bool {
+        self.inner.capabilities.iter().any(|c| c == capability)
+    }
+
+    #[doc(hidden)]
+    fn request_msg(&self, msg: Message<Request, Body<Vec<u8>, io::Error>>) ->
+        Box<Future<Item = Message<Response, Body<Vec<u8>, io::Error>>, Error = Error>>
+    {
+        self.call(msg)
+    }
+}
+
+impl<T> Service for RemoteHost<T> {
+    type Request = Message<Request, Body<Vec<u8>, io::Error>>;
+    type Response = Message<Response, Body<Vec<u8>, io::Error>>;
+    type Error = Error;
+    type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
+
+    fn call(&self, req: Self::Request) -> Self::Future {
+        self.inner.call(req)
+    }
+}
+
+impl ChildIo {
+    fn spawn(cmd: &mut process::Command, handle: &Handle) -> io::Result<ChildIo> {
+        let mut child = cmd.stdin(process::Stdio::piped())
+            .stdout(process::Stdio::piped())
+            .spawn_async(handle)?;
+
+        Ok(ChildIo {
+            stdin: child.stdin().take().unwrap(),
+            stdout: child.stdout().take().unwrap(),
+            _child: child,
+        })
+    }
+}
+
+impl Read for ChildIo {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stdout.read(buf)
+    }
+}
+
+impl Write for ChildIo {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stdin.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stdin.flush()
+    }
+}
+
+impl AsyncRead for ChildIo {}
+
+impl AsyncWrite for ChildIo {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.stdin.shutdown()
+    }
+}
+
+impl Encoding {
+    fn name(&self) -> &'static str {
+        match *self {
+            Encoding::Cbor => "CBOR",
+            Encoding::Json => "JSON",
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl WireCodec {
+    fn new(encoding: Encoding) -> WireCodec {
+        WireCodec {
+            encoding: encoding,
+            decoding_head: true,
+            partial: Vec::new(),
+        }
+    }
+
+    fn decode_header(&self, bytes: &[u8]) -> io::Result<serde_json::Value> {
+        match self.encoding {
+            Encoding::Cbor => serde_cbor::from_slice(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            Encoding::Json => serde_json::from_slice(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        }
+    }
+
+    fn encode_header(&self, header: &serde_json::Value) -> io::Result<Vec<u8>> {
+        match self.encoding {
+            Encoding::Cbor => serde_cbor::to_vec(header).map_err(io::Error::other),
+            Encoding::Json => serde_json::to_vec(header).map_err(io::Error::other),
+        }
+    }
+}
+
+impl Decoder for WireCodec {
     type Item = Frame<serde_json::Value, Vec<u8>, io::Error>;
     type Error = io::Error;
 
     fn decode(&mut self, buf: &mut BytesMut) -> io::Result<Option<Self::Item>> {
-        let line = match buf.iter().position(|b| *b == b'\n') {
-            Some(n) => buf.split_to(n),
-            None => return Ok(None),
-        };
-
-        buf.split_to(1);
+        if buf.len() < FRAME_HEADER_LEN {
+            return Ok(None);
+        }
 
-        if self.decoding_head {
-            debug!("Decoding header: {:?}", line);
+        let kind = buf[0];
+        let len = io::Cursor::new(&buf[1..FRAME_HEADER_LEN]).get_u32_be() as usize;
 
-            // The last byte in this frame is a bool that indicates
-            // whether we have a body stream following or not.
-            // This byte must exist, or our codec is buggered and
-            // panicking is appropriate.
-            let (has_body, line) = line.split_last()
-                .expect("Missing body byte at end of message frame");
+        if len > MAX_FRAME_LEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Frame length {} exceeds maximum of {}", len, MAX_FRAME_LEN)));
+        }
 
-            debug!("Body byte: {:?}", has_body);
+        if buf.len() < FRAME_HEADER_LEN + len {
+            buf.reserve(FRAME_HEADER_LEN + len);
+            return Ok(None);
+        }
 
-            if *has_body == 1 {
-                self.decoding_head = false;
-            }
+        buf.split_to(FRAME_HEADER_LEN);
+        let payload = buf.split_to(len);
 
-            let frame = Frame::Message {
-                message: serde_json::from_slice(&line).map_err(|e| {
-                    io::Error::new(io::ErrorKind::Other, e)
-                })?,
-                body: *has_body == 1,
-            };
+        let frame = match (kind, self.decoding_head) {
+            (FRAME_MESSAGE, true) | (FRAME_MESSAGE_WITH_BODY, true) => {
+                let body = kind == FRAME_MESSAGE_WITH_BODY;
+                if body {
+                    self.decoding_head = false;
+                }
 
-            debug!("Decoded header: {:?}", frame);
+                Frame::Message {
+                    message: self.decode_header(&payload)?,
+                    body: body,
+                }
+            },
+            (FRAME_BODY_PART, false) | (FRAME_BODY_CHUNK, false) => {
+                if self.partial.len() + payload.len() > MAX_CHUNK_LEN {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Body chunk exceeds maximum length of {}", MAX_CHUNK_LEN)));
+                }
+                self.partial.extend_from_slice(&payload);
 
-            Ok(Some(frame))
-        } else {
-            debug!("Decoding body chunk: {:?}", line);
+                if kind == FRAME_BODY_PART {
+                    // The rest of the chunk may already be buffered
+                    return self.decode(buf);
+                }
 
-            let frame = if line.is_empty() {
+                Frame::Body { chunk: Some(mem::take(&mut self.partial)) }
+            },
+            (FRAME_BODY_END, false) if self.partial.is_empty() => {
                 self.decoding_head = true;
                 Frame::Body { chunk: None }
-            } else {
-                Frame::Body { chunk: Some(line.to_vec()) }
-            };
+            },
+            // An error can interrupt a message at any point, and ends it
+            (FRAME_ERROR, _) => {
+                self.decoding_head = true;
+                self.partial.clear();
 
-            debug!("Decoded body chunk: {:?}", frame);
+                let value = self.decode_header(&payload)?;
+                let error: serializable::Error = serde_json::from_value(value)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
 
-            Ok(Some(frame))
-        }
+                warn!("Peer sent error: {}", error);
+
+                Frame::Error { error: error.into() }
+            },
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Unexpected frame type {}", kind))),
+        };
+
+        debug!("Decoded frame: {:?}", frame);
+
+        Ok(Some(frame))
     }
 }
 
-impl Encoder for JsonLineCodec {
+impl Encoder for WireCodec {
     type Item = Frame<serde_json::Value, Vec<u8>, io::Error>;
     type Error = io::Error;
 
     fn encode(&mut self, msg: Self::Item, buf: &mut BytesMut) -> io::Result<()> {
-        match msg {
-            Frame::Message { message, body } => {
-                debug!("Encoding header: {:?}, {:?}", message, body);
+        debug!("Encoding frame: {:?}", msg);
 
-                let json = serde_json::to_vec(&message)
-                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-                buf.extend(&json);
-                // Add 'has-body' flag
-                buf.extend(if body { &[1] } else { &[0] });
-            }
-            Frame::Body { chunk } => {
-                debug!("Encoding chunk: {:?}", chunk);
+        let (kind, payload) = match msg {
+            Frame::Message { message, body } => {
+                let kind = if body { FRAME_MESSAGE_WITH_BODY } else { FRAME_MESSAGE };
+                (kind, self.encode_header(&message)?)
+            },
+            Frame::Body { chunk: Some(chunk) } => {
+                if chunk.len() > MAX_CHUNK_LEN {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Body chunk length {} exceeds maximum of {}", chunk.len(), MAX_CHUNK_LEN)));
+                }
 
-                if let Some(chunk) = chunk {
-                    buf.extend(&chunk);
+                // Send whatever doesn't fit in one frame as parts, so that
+                // the far end still sees a single chunk.
+                let mut parts = chunk.chunks(MAX_FRAME_LEN);
+                let last = parts.next_back().unwrap_or(&[]);
+                for part in parts {
+                    put_frame(buf, FRAME_BODY_PART, part);
                 }
-            }
+                put_frame(buf, FRAME_BODY_CHUNK, last);
+                return Ok(());
+            },
+            Frame::Body { chunk: None } => (FRAME_BODY_END, Vec::new()),
             Frame::Error { error } => {
-                // @todo Support error frames
-                return Err(error)
-            }
-        }
+                let value = serde_json::to_value(serializable::Error::from(error))
+                    .map_err(io::Error::other)?;
+                (FRAME_ERROR, self.encode_header(&value)?)
+            },
+        };
 
-        buf.extend(b"\n");
+        if payload.len() > MAX_FRAME_LEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Frame length {} exceeds maximum of {}", payload.len(), MAX_FRAME_LEN)));
+        }
 
+        put_frame(buf, kind, &payload);
         Ok(())
     }
 }
 
-impl<T: AsyncRead + AsyncWrite + 'static> ClientProto<T> for JsonLineProto {
+fn put_frame(buf: &mut BytesMut, kind: u8, payload: &[u8]) {
+    buf.reserve(FRAME_HEADER_LEN + payload.len());
+    buf.put_u8(kind);
+    buf.put_u32_be(payload.len() as u32);
+    buf.extend(payload);
+}
+
+impl WireProto {
+    #[doc(hidden)]
+    pub fn new(encoding: Encoding) -> WireProto {
+        WireProto { encoding: encoding }
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite + 'static> ClientProto<T> for WireProto {
     type Request = serde_json::Value;
     type RequestBody = Vec<u8>;
     type Response = serde_json::Value;
     type ResponseBody = Vec<u8>;
     type Error = io::Error;
-    type Transport = Framed<T, JsonLineCodec>;
+    type Transport = Framed<T, WireCodec>;
     type BindTransport = result::Result<Self::Transport, Self::Error>;
 
     fn bind_transport(&self, io: T) -> Self::BindTransport {
-        let codec = JsonLineCodec {
-            decoding_head: true,
-        };
-
-        Ok(io.framed(codec))
+        Ok(io.framed(WireCodec::new(self.encoding)))
     }
 }
 
-impl<T: AsyncRead + AsyncWrite + 'static> ServerProto<T> for JsonLineProto {
+impl<T: AsyncRead + AsyncWrite + 'static> ServerProto<T> for WireProto {
     type Request = serde_json::Value;
     type RequestBody = Vec<u8>;
     type Response = serde_json::Value;
     type ResponseBody = Vec<u8>;
     type Error = io::Error;
-    type Transport = Framed<T, JsonLineCodec>;
+    type Transport = Framed<T, WireCodec>;
     type BindTransport = result::Result<Self::Transport, Self::Error>;
 
     fn bind_transport(&self, io: T) -> Self::BindTransport {
-        let codec = JsonLineCodec {
-            decoding_head: true,
-        };
+        Ok(io.framed(WireCodec::new(self.encoding)))
+    }
+}
+
+fn tls_connector(ca: &Path, identity: Option<(&Path, &Path)>) -> Result<SslConnector> {
+    let mut builder = SslConnector::builder(SslMethod::tls())
+        .chain_err(|| "Could not create TLS connector")?;
+    builder.set_ca_file(ca).chain_err(|| "Could not load CA certificate")?;
+
+    if let Some((cert, key)) = identity {
+        builder.set_certificate_chain_file(cert)
+            .chain_err(|| "Could not load client certificate")?;
+        builder.set_private_key_file(key, SslFiletype::PEM)
+            .chain_err(|| "Could not load client private key")?;
+        builder.check_private_key()
+            .chain_err(|| "Client private key does not match certificate")?;
+    }
+
+    Ok(builder.build())
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::{BufMut, BytesMut};
+    use std::io;
+    use super::*;
+    use tokio_io::codec::{Decoder, Encoder};
+    use tokio_proto::streaming::pipeline::Frame;
+
+    fn value(json: &str) -> serde_json::Value {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn roundtrip(encoding: Encoding, frame: Frame<serde_json::Value, Vec<u8>, io::Error>) -> Frame<serde_json::Value, Vec<u8>, io::Error> {
+        let mut codec = WireCodec::new(encoding);
+        let mut buf = BytesMut::new();
+        codec.encode(frame, &mut buf).unwrap();
+        let frame = codec.decode(&mut buf).unwrap().unwrap();
+        assert!(buf.is_empty());
+        frame
+    }
+
+    #[test]
+    fn test_message_roundtrip() {
+        for &encoding in &[Encoding::Json, Encoding::Cbor] {
+            match roundtrip(encoding, Frame::Message { message: value(r#"{"a": [1, 2]}"#), body: false }) {
+                Frame::Message { message, body } => {
+                    assert_eq!(message, value(r#"{"a": [1, 2]}"#));
+                    assert!(!body);
+                },
+                f => panic!("Unexpected frame {:?}", f),
+            }
+        }
+    }
+
+    #[test]
+    fn test_body_frames() {
+        let mut codec = WireCodec::new(Encoding::Json);
+        let mut buf = BytesMut::new();
+        codec.encode(Frame::Message { message: value(r#""head""#), body: true }, &mut buf).unwrap();
+        codec.encode(Frame::Body { chunk: Some(b"chunk".to_vec()) }, &mut buf).unwrap();
+        codec.encode(Frame::Body { chunk: None }, &mut buf).unwrap();
+        codec.encode(Frame::Message { message: value(r#""next""#), body: false }, &mut buf).unwrap();
+
+        match codec.decode(&mut buf).unwrap() {
+            Some(Frame::Message { body: true, .. }) => (),
+            f => panic!("Unexpected frame {:?}", f),
+        }
+        match codec.decode(&mut buf).unwrap() {
+            Some(Frame::Body { chunk: Some(ref c) }) if c == b"chunk" => (),
+            f => panic!("Unexpected frame {:?}", f),
+        }
+        match codec.decode(&mut buf).unwrap() {
+            Some(Frame::Body { chunk: None }) => (),
+            f => panic!("Unexpected frame {:?}", f),
+        }
+        match codec.decode(&mut buf).unwrap() {
+            Some(Frame::Message { ref message, body: false }) if *message == value(r#""next""#) => (),
+            f => panic!("Unexpected frame {:?}", f),
+        }
+    }
+
+    #[test]
+    fn test_partial_frame() {
+        let mut codec = WireCodec::new(Encoding::Json);
+        let mut full = BytesMut::new();
+        codec.encode(Frame::Message { message: value(r#""partial""#), body: false }, &mut full).unwrap();
+
+        let mut buf = BytesMut::new();
+        buf.extend(&full[..3]);
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+        buf.extend(&full[3..full.len() - 1]);
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+        buf.extend(&full[full.len() - 1..]);
+        assert!(codec.decode(&mut buf).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_body_chunk_without_message() {
+        let mut codec = WireCodec::new(Encoding::Json);
+        let mut buf = BytesMut::new();
+        codec.encode(Frame::Body { chunk: Some(b"stray".to_vec()) }, &mut buf).unwrap();
+        assert!(codec.decode(&mut buf).is_err());
+    }
+
+    #[test]
+    fn test_max_frame_len() {
+        let mut codec = WireCodec::new(Encoding::Json);
+
+        // Oversized frames are rejected from their header alone, before
+        // we've buffered any of the payload.
+        let mut buf = BytesMut::new();
+        buf.put_u8(FRAME_MESSAGE);
+        buf.put_u32_be(MAX_FRAME_LEN as u32 + 1);
+        let err = codec.decode(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let mut buf = BytesMut::new();
+        let chunk = vec![0; MAX_FRAME_LEN];
+        codec.encode(Frame::Body { chunk: Some(chunk) }, &mut buf).unwrap();
+        assert_eq!(buf.len(), FRAME_HEADER_LEN + MAX_FRAME_LEN);
+    }
+
+    #[test]
+    fn test_body_chunk_parts() {
+        let mut codec = WireCodec::new(Encoding::Json);
+        let mut buf = BytesMut::new();
+        codec.encode(Frame::Message { message: value(r#""head""#), body: true }, &mut buf).unwrap();
+        codec.decode(&mut buf).unwrap().unwrap();
+
+        // Chunks over the limit are split across frames...
+        let mut chunk = vec![0; MAX_FRAME_LEN + 10];
+        chunk[MAX_FRAME_LEN - 1] = 1;
+        chunk[MAX_FRAME_LEN] = 2;
+        codec.encode(Frame::Body { chunk: Some(chunk.clone()) }, &mut buf).unwrap();
+        codec.encode(Frame::Body { chunk: Some(b"next".to_vec()) }, &mut buf).unwrap();
+        assert_eq!(buf.len(), 3 * FRAME_HEADER_LEN + chunk.len() + 4);
+        assert_eq!(buf[0], FRAME_BODY_PART);
+
+        // ...and joined back together, even if they arrive piecemeal
+        let mut partial = buf.split_to(FRAME_HEADER_LEN + MAX_FRAME_LEN + 1);
+        assert!(codec.decode(&mut partial).unwrap().is_none());
+        partial.extend(&buf);
+        match codec.decode(&mut partial).unwrap() {
+            Some(Frame::Body { chunk: Some(ref c) }) => assert!(*c == chunk),
+            f => panic!("Unexpected frame {:?}", f.map(|_| ())),
+        }
+        match codec.decode(&mut partial).unwrap() {
+            Some(Frame::Body { chunk: Some(ref c) }) if c == b"next" => (),
+            f => panic!("Unexpected frame {:?}", f),
+        }
+        assert!(partial.is_empty());
 
-        Ok(io.framed(codec))
+        // A body can't end half way through a chunk
+        let mut buf = BytesMut::new();
+        put_frame(&mut buf, FRAME_BODY_PART, b"part");
+        codec.encode(Frame::Body { chunk: None }, &mut buf).unwrap();
+        assert!(codec.decode(&mut buf).is_err());
     }
 }
@@ -41,7 +41,7 @@
 //! order to do anything.
 //!
 //! Hosts come in both the [`Local`](host/local/struct.Local.html) and
-//! [`Plain`](host/remote/struct.Plain.html) varieties. The `Local` type points
+//! [`Plain`](host/remote/type.Plain.html) varieties. The `Local` type points
 //! to your local machine, and the `Plain` type is a remote host type that
 //! connects to a remote machine over the network. Whichever type you choose,
 //! simply pass it in to your endpoints as required and Intecture will do the
@@ -50,6 +50,16 @@
 //!>“Why `Plain`?” I hear you ask. Well, it’s because the `Plain` host type is
 //! a remote host that uses TCP to send/receive _plaintext_ data.
 //!
+//! If your hosts live on a network you don't trust, use the
+//! [`Tls`](host/remote/type.Tls.html) type instead, which encrypts the
+//! connection and verifies the agent's certificate. Or if you'd rather not
+//! run a listening agent at all, the [`Ssh`](host/remote/type.Ssh.html)
+//! type tunnels everything through an SSH connection. More generally, the
+//! [`Process`](host/remote/type.Process.html) type will talk to an agent
+//! through any command that gives you a shell, like `sudo` or `docker exec`,
+//! and the [`Unix`](host/remote/type.Unix.html) type connects to an agent
+//! listening on a local Unix domain socket.
+//!
 //!## Example
 //!
 //! Here’s a reproduction of the
@@ -79,15 +89,20 @@
 //!
 //!        // Let's start with something basic - a shell command.
 //!        let cmd = Command::new(&host, "whoami", None);
-//!        cmd.exec().and_then(|mut status| {
+//!        cmd.exec().and_then(|status| {
+//!            // Commands can be made idempotent with guards like `creates()`,
+//!            // in which case `exec()` returns `None` if there's nothing to
+//!            // do. We haven't set any, so our command always runs.
+//!            let mut status = status.unwrap();
+//!
 //!            // At this point, our command is running. As the API is
 //!            // asynchronous, we don't have to wait for it to finish before
 //!            // inspecting its output. This is called "streaming".
 //!
-//!            // First let's grab the stream from `CommandStatus`. This stream is
-//!            // a stream of strings, each of which represents a line of command
-//!            // output. We can use the `for_each` combinator to print these
-//!            // lines to stdout.
+//!            // First let's grab the stream from `CommandStatus`. This stream
+//!            // yields each line of command output, tagged with whether it came
+//!            // from stdout or stderr. We can use the `for_each` combinator to
+//!            // print these lines to stdout.
 //!            //
 //!            // If printing isn't your thing, you are also free to lick them or
 //!            // whatever you're into. I'm not here to judge.
@@ -129,17 +144,24 @@ extern crate erased_serde;
 extern crate futures;
 extern crate hostname;
 extern crate ipnetwork;
+extern crate libc;
 #[macro_use] extern crate log;
+extern crate openssl;
 extern crate pnet;
 extern crate regex;
 extern crate serde;
+extern crate serde_cbor;
 #[macro_use] extern crate serde_derive;
 extern crate serde_json;
+extern crate tempfile;
 extern crate tokio_core;
+extern crate tokio_file_unix;
 extern crate tokio_io;
+extern crate tokio_openssl;
 extern crate tokio_process;
 extern crate tokio_proto;
 extern crate tokio_service;
+extern crate tokio_uds;
 extern crate users;
 
 pub mod command;
@@ -149,14 +171,16 @@ pub mod prelude {
     //! The API prelude.
     pub use command::{self, Command};
     pub use host::Host;
-    pub use host::remote::{self, Plain};
+    pub use host::remote::{self, Plain, Process, Ssh, Tls, Unix};
     pub use host::local::{self, Local};
-    pub use package::{self, Package};
+    pub use package::{self, Package, PackageCache, PackageSet};
+    pub use repository::{self, Repository};
     pub use service::{self, Service};
     pub use telemetry::{self, Cpu, FsMount, LinuxDistro, Os, OsFamily, OsPlatform, Telemetry};
 }
 pub mod package;
 #[doc(hidden)] pub mod remote;
+pub mod repository;
 pub mod service;
 mod target;
 pub mod telemetry;
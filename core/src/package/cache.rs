@@ -0,0 +1,142 @@
+// Copyright 2015-2017 Intecture Developers.
+//
+// Licensed under the Mozilla Public License 2.0 <LICENSE or
+// https://www.tldrlegal.com/l/mpl-2.0>. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+use command::CommandStatus;
+use errors::*;
+use futures::{future, Future};
+use host::Host;
+use remote::{Request, Response};
+use std::time::Duration;
+use super::{Provider, MAX_OUTPUT};
+
+/// Represents a host's package index, i.e. the list of packages that its
+/// package manager knows about.
+///
+/// Package managers install packages from their index, so if it's missing
+/// or stale, as it often is on fresh cloud images, installs can fail.
+///
+///# Example
+///
+/// Update the index if it hasn't been updated in the last day, then install
+/// a package.
+///
+///```no_run
+///extern crate futures;
+///extern crate intecture_api;
+///extern crate tokio_core;
+///
+///use futures::{future, Future};
+///use intecture_api::errors::*;
+///use intecture_api::prelude::*;
+///use std::time::Duration;
+///use tokio_core::reactor::Core;
+///
+///# fn main() {
+///let mut core = Core::new().unwrap();
+///let handle = core.handle();
+///
+///let host = Local::new(&handle).wait().unwrap();
+///
+///let cache = PackageCache::new(&host);
+///let nginx = Package::new(&host, "nginx");
+///let result = cache.update(Some(Duration::from_secs(86400)))
+///    .and_then(|status| match status {
+///        Some(status) => Box::new(status.result().unwrap().map(|_| ())) as Box<Future<Item = _, Error = Error>>,
+///        None => Box::new(future::ok(())),
+///    })
+///    .and_then(move |_| nginx.install());
+///
+///core.run(result).unwrap();
+///# }
+///```
+pub struct PackageCache<H: Host> {
+    host: H,
+    provider: Option<Provider>,
+}
+
+impl<H: Host + 'static> PackageCache<H> {
+    /// Create a new `PackageCache` with the default [`Provider`](enum.Provider.html).
+    pub fn new(host: &H) -> PackageCache<H> {
+        PackageCache {
+            host: host.clone(),
+            provider: None,
+        }
+    }
+
+    /// Create a new `PackageCache` with the specified [`Provider`](enum.Provider.html).
+    pub fn with_provider(host: &H, provider: Provider) -> PackageCache<H> {
+        PackageCache {
+            host: host.clone(),
+            provider: Some(provider),
+        }
+    }
+
+    /// Get the time since the index was last updated, or `None` if the
+    /// provider can't tell, e.g. because it has never been updated.
+    ///
+    /// This is based on the timestamps of the provider's cache files, so
+    /// it may also change when the package manager updates the index by
+    /// itself.
+    pub fn age(&self) -> Box<Future<Item = Option<Duration>, Error = Error>> {
+        let request = Request::PackageCacheAge(self.provider);
+        if !self.host.supports(request.name()) {
+            return Box::new(future::err(ErrorKind::Unsupported(request.name().into()).into()));
+        }
+
+        Box::new(self.host.request(request)
+            .chain_err(|| ErrorKind::Request { endpoint: "PackageCache".into(), func: "age".into() })
+            .map(|msg| {
+                match msg.into_inner() {
+                    Response::Age(age) => age,
+                    _ => unreachable!(),
+                }
+            }))
+    }
+
+    /// Update the index, e.g. with `apt-get update`. If `max_age` is given,
+    /// only update it if it's older than that.
+    ///
+    ///## Idempotence
+    ///
+    /// If `max_age` is given, this function is idempotent, which is
+    /// represented by the type `Future<Item = Option<..>, ...>`. Thus if it
+    /// returns `Option::None` then the index is fresh enough, and if it
+    /// returns `Option::Some` then Intecture is attempting to update it.
+    /// Without `max_age`, the index is always updated.
+    ///
+    /// If this fn returns `Option::Some<..>`, the
+    /// [`CommandStatus`](../command/struct.CommandStatus.html) will hold
+    /// handles to the live output and the result of the update.
+    pub fn update(&self, max_age: Option<Duration>) -> Box<Future<Item = Option<CommandStatus>, Error = Error>> {
+        let host = self.host.clone();
+        let provider = self.provider;
+
+        let needed: Box<Future<Item = bool, Error = Error>> = match max_age {
+            Some(max) => Box::new(self.age().map(move |age| match age {
+                Some(age) => age >= max,
+                None => true,
+            })),
+            None => Box::new(future::ok(true)),
+        };
+
+        Box::new(needed.and_then(move |needed| {
+            if !needed {
+                return Box::new(future::ok(None)) as Box<Future<Item = _, Error = Error>>;
+            }
+
+            let request = Request::PackageCacheUpdate(provider);
+            if !host.supports(request.name()) {
+                return Box::new(future::err(ErrorKind::Unsupported(request.name().into()).into()));
+            }
+
+            Box::new(host.request(request)
+                .chain_err(|| ErrorKind::Request { endpoint: "PackageCache".into(), func: "update".into() })
+                .and_then(move |msg| {
+                    CommandStatus::new(&host, msg).map(|status| Some(status.limit_output(MAX_OUTPUT)))
+                }))
+        }))
+    }
+}
@@ -7,9 +7,13 @@
 //! Endpoint for managing packages.
 //!
 //! A package is represented by the `Package` struct, which is idempotent. This
-//! means you can execute it repeatedly and it'll only run as needed.
+//! means you can execute it repeatedly and it'll only run as needed. To
+//! manage lots of packages at once, use `PackageSet`. To refresh the host's
+//! package index, use `PackageCache`.
 
+mod cache;
 mod providers;
+mod set;
 
 use command::CommandStatus;
 use errors::*;
@@ -18,7 +22,14 @@ use host::Host;
 use remote::{Request, Response};
 #[doc(hidden)]
 pub use self::providers::{factory, PackageProvider, Apt, Dnf, Homebrew, Nix, Pkg, Yum};
+pub use self::cache::PackageCache;
 pub use self::providers::Provider;
+pub use self::set::PackageSet;
+
+/// The most stdout and stderr that `CommandStatus::result()` keeps for
+/// package operations, which can print a great deal when building from
+/// source. Use `CommandStatus::result_with()` to keep more.
+pub const MAX_OUTPUT: usize = 1024 * 1024;
 
 /// Represents a system package to be managed for a host.
 ///
@@ -50,7 +61,7 @@ pub use self::providers::Provider;
 ///            .map(|_| println!("Installed"))
 ///            .map_err(|e| {
 ///                match *e.kind() {
-///                    ErrorKind::Command(ref output) => println!("Failed with output: {}", output),
+///                    ErrorKind::Command { ref stderr, .. } => println!("Failed with output: {}", stderr),
 ///                    _ => unreachable!(),
 ///                }
 ///                e
@@ -67,10 +78,12 @@ pub use self::providers::Provider;
 ///core.run(result).unwrap();
 ///# }
 ///```
+#[derive(Clone)]
 pub struct Package<H: Host> {
     host: H,
     provider: Option<Provider>,
     name: String,
+    version: Option<String>,
 }
 
 impl<H: Host + 'static> Package<H> {
@@ -80,6 +93,7 @@ impl<H: Host + 'static> Package<H> {
             host: host.clone(),
             provider: None,
             name: name.into(),
+            version: None,
         }
     }
 
@@ -109,14 +123,92 @@ impl<H: Host + 'static> Package<H> {
             host: host.clone(),
             provider: Some(provider),
             name: name.into(),
+            version: None,
         }
     }
 
+    /// Pin the package to `version`, which is either an exact version or a
+    /// constraint with a trailing wildcard, like `1.18.*`.
+    ///
+    /// `install()` will then install this version, and considers the package
+    /// installed only if the installed version matches. Versions are given
+    /// in the provider's own format, e.g. Apt versions may include a Debian
+    /// revision (`1.18.0-0ubuntu1`). Each provider has its own quirks:
+    ///
+    /// - Homebrew only has specific versions of some packages, as separate
+    ///   formulae. Pinning `nginx` to `1.18.*` installs `nginx@1.18`, and
+    ///   exact versions like `1.18.0` are rejected.
+    /// - Nix can only install exact versions, not constraints.
+    ///
+    ///## Example
+    ///```
+    ///extern crate futures;
+    ///extern crate intecture_api;
+    ///extern crate tokio_core;
+    ///
+    ///use futures::Future;
+    ///use intecture_api::prelude::*;
+    ///use tokio_core::reactor::Core;
+    ///
+    ///# fn main() {
+    ///let mut core = Core::new().unwrap();
+    ///let handle = core.handle();
+    ///
+    ///let host = Local::new(&handle).wait().unwrap();
+    ///
+    ///let nginx = Package::new(&host, "nginx").pin("1.18.*");
+    ///# }
+    pub fn pin(mut self, version: &str) -> Package<H> {
+        self.version = Some(version.into());
+        self
+    }
+
     /// Check if the package is installed.
     pub fn installed(&self) -> Box<Future<Item = bool, Error = Error>> {
         let request = Request::PackageInstalled(self.provider, self.name.clone());
         Box::new(self.host.request(request)
-            .chain_err(|| ErrorKind::Request { endpoint: "Package", func: "installed" })
+            .chain_err(|| ErrorKind::Request { endpoint: "Package".into(), func: "installed".into() })
+            .map(|msg| {
+                match msg.into_inner() {
+                    Response::Bool(b) => b,
+                    _ => unreachable!(),
+                }
+            }))
+    }
+
+    /// Get the installed version of the package, or `None` if it isn't
+    /// installed. For a package pinned with `pin()`, providers that install
+    /// pinned versions separately (i.e. Homebrew's versioned formulae) report
+    /// that version instead.
+    pub fn version(&self) -> Box<Future<Item = Option<String>, Error = Error>> {
+        let request = Request::PackageVersion(self.provider, self.name.clone(), self.version.clone());
+        if !self.host.supports(request.name()) {
+            return Box::new(future::err(ErrorKind::Unsupported(request.name().into()).into()));
+        }
+
+        Box::new(self.host.request(request)
+            .chain_err(|| ErrorKind::Request { endpoint: "Package".into(), func: "version".into() })
+            .map(|msg| {
+                match msg.into_inner() {
+                    Response::Version(v) => v,
+                    _ => unreachable!(),
+                }
+            }))
+    }
+
+    /// Check if a newer version of the package is available. Packages that
+    /// aren't installed are never outdated.
+    ///
+    /// This relies on the host's package cache, so it won't know about
+    /// versions released since the cache was last updated.
+    pub fn is_outdated(&self) -> Box<Future<Item = bool, Error = Error>> {
+        let request = Request::PackageOutdated(self.provider, self.name.clone());
+        if !self.host.supports(request.name()) {
+            return Box::new(future::err(ErrorKind::Unsupported(request.name().into()).into()));
+        }
+
+        Box::new(self.host.request(request)
+            .chain_err(|| ErrorKind::Request { endpoint: "Package".into(), func: "is_outdated".into() })
             .map(|msg| {
                 match msg.into_inner() {
                     Response::Bool(b) => b,
@@ -125,6 +217,21 @@ impl<H: Host + 'static> Package<H> {
             }))
     }
 
+    // Whether the package needs installing. Pinned packages also need
+    // installing if the wrong version is installed.
+    fn needs_install(&self) -> Box<Future<Item = bool, Error = Error>> {
+        match self.version {
+            Some(ref wanted) => {
+                let wanted = wanted.clone();
+                Box::new(self.version().map(move |installed| match installed {
+                    Some(v) => !version_matches(&v, &wanted),
+                    None => true,
+                }))
+            },
+            None => Box::new(self.installed().map(|installed| !installed)),
+        }
+    }
+
     /// Install the package.
     ///
     ///## Idempotence
@@ -132,28 +239,32 @@ impl<H: Host + 'static> Package<H> {
     /// This function is idempotent, which is represented by the type
     /// `Future<Item = Option<..>, ...>`. Thus if it returns `Option::None`
     /// then the package is already installed, and if it returns `Option::Some`
-    /// then Intecture is attempting to install the package.
+    /// then Intecture is attempting to install the package. If the package
+    /// is pinned with `pin()`, installing a different version counts as not
+    /// being installed.
     ///
     /// If this fn returns `Option::Some<..>`, the nested tuple will hold
     /// handles to the live output and the result of the installation. Under
     /// the hood this reuses the `Command` endpoint, so see
     /// [`Command` docs](../command/struct.Command.html) for detailed
-    /// usage.
+    /// usage. Its `result()` keeps the last
+    /// [`MAX_OUTPUT`](constant.MAX_OUTPUT.html) bytes of output.
     pub fn install(&self) -> Box<Future<Item = Option<CommandStatus>, Error = Error>>
     {
         let host = self.host.clone();
         let provider = self.provider;
         let name = self.name.clone();
+        let version = self.version.clone();
 
-        Box::new(self.installed()
-            .and_then(move |installed| {
-                if installed {
+        Box::new(self.needs_install()
+            .and_then(move |needed| {
+                if !needed {
                     Box::new(future::ok(None)) as Box<Future<Item = _, Error = Error>>
                 } else {
-                    Box::new(host.request(Request::PackageInstall(provider, name))
-                        .chain_err(|| ErrorKind::Request { endpoint: "Package", func: "install" })
-                        .map(|msg| {
-                            Some(CommandStatus::new(msg))
+                    Box::new(host.request(Request::PackageInstall(provider, name, version))
+                        .chain_err(|| ErrorKind::Request { endpoint: "Package".into(), func: "install".into() })
+                        .and_then(move |msg| {
+                            CommandStatus::new(&host, msg).map(|status| Some(status.limit_output(MAX_OUTPUT)))
                         }))
                 }
             }))
@@ -183,13 +294,129 @@ impl<H: Host + 'static> Package<H> {
             .and_then(move |installed| {
                 if installed {
                     Box::new(host.request(Request::PackageUninstall(provider, name))
-                        .chain_err(|| ErrorKind::Request { endpoint: "Package", func: "uninstall" })
-                        .map(|msg| {
-                            Some(CommandStatus::new(msg))
+                        .chain_err(|| ErrorKind::Request { endpoint: "Package".into(), func: "uninstall".into() })
+                        .and_then(move |msg| {
+                            CommandStatus::new(&host, msg).map(|status| Some(status.limit_output(MAX_OUTPUT)))
                         }))
                 } else {
                     Box::new(future::ok(None)) as Box<Future<Item = _, Error = Error>>
                 }
             }))
     }
+
+    /// Upgrade the package to the latest version, installing it if it
+    /// isn't installed already.
+    ///
+    ///## Idempotence
+    ///
+    /// This function is idempotent, which is represented by the type
+    /// `Future<Item = Option<..>, ...>`. Thus if it returns `Option::None`
+    /// then the latest version is already installed, and if it returns
+    /// `Option::Some` then Intecture is attempting to install or upgrade the
+    /// package. Packages pinned with `pin()` can't be upgraded, so this
+    /// returns an error for them.
+    ///
+    /// If this fn returns `Option::Some<..>`, the nested tuple will hold
+    /// handles to the live output and the result of the upgrade. Under the
+    /// hood this reuses the `Command` endpoint, so see
+    /// [`Command` docs](../command/struct.Command.html) for detailed
+    /// usage.
+    pub fn upgrade(&self) -> Box<Future<Item = Option<CommandStatus>, Error = Error>>
+    {
+        if let Some(ref version) = self.version {
+            return Box::new(future::err(format!("Cannot upgrade {}, as it is pinned to version {}", self.name, version).into()));
+        }
+
+        let host = self.host.clone();
+        let provider = self.provider;
+        let name = self.name.clone();
+        let package = self.clone();
+
+        Box::new(self.installed()
+            .and_then(move |installed| {
+                if !installed {
+                    Box::new(host.request(Request::PackageInstall(provider, name, None))
+                        .chain_err(|| ErrorKind::Request { endpoint: "Package".into(), func: "upgrade".into() })
+                        .and_then(move |msg| {
+                            CommandStatus::new(&host, msg).map(|status| Some(status.limit_output(MAX_OUTPUT)))
+                        })) as Box<Future<Item = _, Error = Error>>
+                } else {
+                    Box::new(package.is_outdated().and_then(move |outdated| {
+                        if outdated {
+                            Box::new(host.request(Request::PackageUpgrade(provider, name))
+                                .chain_err(|| ErrorKind::Request { endpoint: "Package".into(), func: "upgrade".into() })
+                                .and_then(move |msg| {
+                                    CommandStatus::new(&host, msg).map(|status| Some(status.limit_output(MAX_OUTPUT)))
+                                })) as Box<Future<Item = _, Error = Error>>
+                        } else {
+                            Box::new(future::ok(None))
+                        }
+                    }))
+                }
+            }))
+    }
+}
+
+// Check an installed version against one we asked for. Constraints with
+// wildcards must match the whole version. Exact versions may leave off the
+// revision that packagers add, e.g. "1.18.0" matches "1.18.0-0ubuntu1".
+fn version_matches(installed: &str, wanted: &str) -> bool {
+    // Ignore the epoch, e.g. "1:" in "1:1.18.0", unless we asked for one
+    let installed = match installed.find(':') {
+        Some(i) if !wanted.contains(':') && installed[..i].chars().all(|c| c.is_ascii_digit()) => &installed[i + 1..],
+        _ => installed,
+    };
+
+    if wanted.contains('*') {
+        let mut parts = wanted.split('*');
+        let first = parts.next().unwrap();
+        if !installed.starts_with(first) {
+            return false;
+        }
+
+        let mut rest = &installed[first.len()..];
+        let last = parts.next_back().unwrap();
+        for part in parts {
+            match rest.find(part) {
+                Some(i) => rest = &rest[i + part.len()..],
+                None => return false,
+            }
+        }
+        rest.ends_with(last)
+    } else {
+        installed == wanted || (installed.starts_with(wanted) && installed[wanted.len()..].starts_with(['-', '_']))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::version_matches;
+
+    #[test]
+    fn test_version_matches_exact() {
+        assert!(version_matches("1.18.0", "1.18.0"));
+        assert!(version_matches("1.18.0-0ubuntu1", "1.18.0"));
+        assert!(version_matches("1.18.0_1", "1.18.0"));
+        assert!(!version_matches("1.18.01", "1.18.0"));
+        assert!(!version_matches("1.18.0", "1.18"));
+        assert!(!version_matches("1.19.0", "1.18.0"));
+    }
+
+    #[test]
+    fn test_version_matches_wildcard() {
+        assert!(version_matches("1.18.0", "1.18.*"));
+        assert!(version_matches("1.18.0-0ubuntu1", "1.18.*"));
+        assert!(!version_matches("1.19.0", "1.18.*"));
+        assert!(!version_matches("11.18.0", "1.18.*"));
+        assert!(version_matches("1.18.0-0ubuntu1", "1.*-0ubuntu*"));
+        assert!(!version_matches("1.18.0-1", "1.*-0ubuntu*"));
+    }
+
+    #[test]
+    fn test_version_matches_epoch() {
+        assert!(version_matches("1:1.18.0-0ubuntu1", "1.18.0"));
+        assert!(version_matches("1:1.18.0", "1:1.18.0"));
+        assert!(!version_matches("1:1.18.0", "2:1.18.0"));
+        assert!(version_matches("2:1.18.0", "1.18.*"));
+    }
 }
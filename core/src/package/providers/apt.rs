@@ -5,13 +5,12 @@
 // modified, or distributed except according to those terms.
 
 use command::factory;
-use error_chain::ChainedError;
 use errors::*;
 use futures::{future, Future};
 use regex::Regex;
 use remote::{ExecutableResult, Response, ResponseResult};
 use std::process;
-use super::PackageProvider;
+use super::{modified_age, PackageProvider};
 use telemetry::Os;
 use tokio_core::reactor::Handle;
 use tokio_process::CommandExt;
@@ -28,6 +27,16 @@ impl PackageProvider for Apt {
             .success())
     }
 
+    fn cache_age(&self, _: &Handle) -> ExecutableResult {
+        // Apt's periodic updates leave a stamp. Otherwise, updating touches
+        // the lists directory.
+        let age = modified_age(&["/var/lib/apt/periodic/update-success-stamp", "/var/lib/apt/lists"]);
+        Box::new(future::ok(
+            Message::WithoutBody(
+                ResponseResult::Ok(
+                    Response::Age(age)))))
+    }
+
     fn installed(&self, handle: &Handle, name: &str, _: &Os) -> ExecutableResult {
         let handle = handle.clone();
         let name = name.to_owned();
@@ -52,7 +61,97 @@ impl PackageProvider for Apt {
                     future::ok(
                         Message::WithoutBody(
                             ResponseResult::Err(
-                                format!("Error running `dpkg --get-selections`: {}", String::from_utf8_lossy(&output.stderr))
+                                format!("Error running `dpkg --get-selections`: {}", String::from_utf8_lossy(&output.stderr)).into()
+                            )
+                        )
+                    )
+                }
+            }))
+    }
+
+    fn installed_many(&self, handle: &Handle, names: &[&str], _: &Os) -> ExecutableResult {
+        let handle = handle.clone();
+        let names: Vec<String> = names.iter().map(|n| (*n).to_owned()).collect();
+        let mut args = vec!["--show".to_owned(), "--showformat".into(), "${Package}\\t${Status}\\n".into(), "--".into()];
+        args.extend(names.iter().cloned());
+
+        Box::new(process::Command::new("dpkg-query")
+            .args(&args)
+            .output_async(&handle)
+            .chain_err(|| "Could not get installed packages")
+            .map(move |output| {
+                // dpkg-query fails if it has never heard of some of the
+                // packages, but still lists the rest.
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                let installed = stdout.lines()
+                    .filter_map(|l| {
+                        let mut fields = l.splitn(2, '\t');
+                        match (fields.next(), fields.next()) {
+                            (Some(name), Some(status)) if status.ends_with(" installed") => Some(name),
+                            _ => None,
+                        }
+                    })
+                    .filter(|n| names.iter().any(|name| name == n))
+                    .map(|n| n.to_owned())
+                    .collect();
+                Message::WithoutBody(
+                    ResponseResult::Ok(
+                        Response::List(installed)))
+            }))
+    }
+
+    fn version(&self, handle: &Handle, name: &str, _: Option<&str>, _: &Os) -> ExecutableResult {
+        let handle = handle.clone();
+
+        Box::new(process::Command::new("dpkg-query")
+            .args(["--show", "--showformat", "${Status}\\t${Version}", "--", name])
+            .output_async(&handle)
+            .chain_err(|| "Could not get installed package version")
+            .map(|output| {
+                // dpkg-query fails for packages it has never heard of
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                let mut fields = stdout.trim().splitn(2, '\t');
+                let version = match (fields.next(), fields.next()) {
+                    (Some(status), Some(version)) if output.status.success() && status.ends_with(" installed") => Some(version.into()),
+                    _ => None,
+                };
+                Message::WithoutBody(
+                    ResponseResult::Ok(
+                        Response::Version(version)))
+            }))
+    }
+
+    fn is_outdated(&self, handle: &Handle, name: &str, _: &Os) -> ExecutableResult {
+        let handle = handle.clone();
+        let name = name.to_owned();
+
+        Box::new(process::Command::new("apt-cache")
+            .args(["policy", "--", &name])
+            .output_async(&handle)
+            .chain_err(|| "Could not check for package upgrades")
+            .and_then(move |output| {
+                if output.status.success() {
+                    // Compare the installed version with the version apt
+                    // would install.
+                    let stdout = String::from_utf8_lossy(&output.stdout);
+                    let field = |key: &str| stdout.lines()
+                        .map(|l| l.trim())
+                        .find(|l| l.starts_with(key))
+                        .map(|l| l[key.len()..].trim().to_owned());
+                    let outdated = match (field("Installed:"), field("Candidate:")) {
+                        (Some(installed), Some(candidate)) => installed != "(none)" && candidate != "(none)" && installed != candidate,
+                        _ => false,
+                    };
+                    future::ok(
+                        Message::WithoutBody(
+                            ResponseResult::Ok(
+                                Response::Bool(
+                                    outdated))))
+                } else {
+                    future::ok(
+                        Message::WithoutBody(
+                            ResponseResult::Err(
+                                format!("Error running `apt-cache policy {}`: {}", name, String::from_utf8_lossy(&output.stderr)).into()
                             )
                         )
                     )
@@ -60,15 +159,33 @@ impl PackageProvider for Apt {
             }))
     }
 
-    fn install(&self, handle: &Handle, name: &str) -> ExecutableResult {
+    fn install(&self, handle: &Handle, name: &str, version: Option<&str>) -> ExecutableResult {
+        let cmd = match factory() {
+            Ok(c) => c,
+            Err(e) => return Box::new(future::ok(
+                Message::WithoutBody(
+                    ResponseResult::Err(
+                        e.into())))),
+        };
+        match version {
+            // Apt needs permission to install a version older than the one
+            // that's installed.
+            Some(v) => cmd.exec(handle, &["apt-get", "-y", "--allow-downgrades", "install", "--", &format!("{}={}", name, v)]),
+            None => cmd.exec(handle, &["apt-get", "-y", "install", "--", name]),
+        }
+    }
+
+    fn install_many(&self, handle: &Handle, names: &[&str]) -> ExecutableResult {
         let cmd = match factory() {
             Ok(c) => c,
             Err(e) => return Box::new(future::ok(
                 Message::WithoutBody(
                     ResponseResult::Err(
-                        format!("{}", e.display_chain()))))),
+                        e.into())))),
         };
-        cmd.exec(handle, &["apt-get", "-y", "install", name])
+        let mut args = vec!["apt-get", "-y", "install", "--"];
+        args.extend_from_slice(names);
+        cmd.exec(handle, &args)
     }
 
     fn uninstall(&self, handle: &Handle, name: &str) -> ExecutableResult {
@@ -77,8 +194,43 @@ impl PackageProvider for Apt {
             Err(e) => return Box::new(future::ok(
                 Message::WithoutBody(
                     ResponseResult::Err(
-                        format!("{}", e.display_chain()))))),
+                        e.into())))),
+        };
+        cmd.exec(handle, &["apt-get", "-y", "remove", "--", name])
+    }
+
+    fn uninstall_many(&self, handle: &Handle, names: &[&str]) -> ExecutableResult {
+        let cmd = match factory() {
+            Ok(c) => c,
+            Err(e) => return Box::new(future::ok(
+                Message::WithoutBody(
+                    ResponseResult::Err(
+                        e.into())))),
+        };
+        let mut args = vec!["apt-get", "-y", "remove", "--"];
+        args.extend_from_slice(names);
+        cmd.exec(handle, &args)
+    }
+
+    fn update_cache(&self, handle: &Handle) -> ExecutableResult {
+        let cmd = match factory() {
+            Ok(c) => c,
+            Err(e) => return Box::new(future::ok(
+                Message::WithoutBody(
+                    ResponseResult::Err(
+                        e.into())))),
+        };
+        cmd.exec(handle, &["apt-get", "update"])
+    }
+
+    fn upgrade(&self, handle: &Handle, name: &str) -> ExecutableResult {
+        let cmd = match factory() {
+            Ok(c) => c,
+            Err(e) => return Box::new(future::ok(
+                Message::WithoutBody(
+                    ResponseResult::Err(
+                        e.into())))),
         };
-        cmd.exec(handle, &["apt-get", "-y", "remove", name])
+        cmd.exec(handle, &["apt-get", "-y", "install", "--only-upgrade", "--", name])
     }
 }
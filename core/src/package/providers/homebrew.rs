@@ -5,13 +5,12 @@
 // modified, or distributed except according to those terms.
 
 use command::factory;
-use error_chain::ChainedError;
 use errors::*;
 use futures::{future, Future};
 use regex::Regex;
 use remote::{ExecutableResult, Response, ResponseResult};
 use std::process;
-use super::PackageProvider;
+use super::{modified_age, PackageProvider};
 use telemetry::Os;
 use tokio_core::reactor::Handle;
 use tokio_process::CommandExt;
@@ -28,6 +27,34 @@ impl PackageProvider for Homebrew {
             .success())
     }
 
+    fn cache_age(&self, handle: &Handle) -> ExecutableResult {
+        let handle = handle.clone();
+
+        Box::new(process::Command::new("brew")
+            .arg("--repository")
+            .output_async(&handle)
+            .chain_err(|| "Could not find Homebrew repository")
+            .and_then(|output| {
+                if output.status.success() {
+                    // `brew update` fetches the Homebrew repository
+                    let repo = String::from_utf8_lossy(&output.stdout);
+                    future::ok(
+                        Message::WithoutBody(
+                            ResponseResult::Ok(
+                                Response::Age(
+                                    modified_age(&[format!("{}/.git/FETCH_HEAD", repo.trim())])))))
+                } else {
+                    future::ok(
+                        Message::WithoutBody(
+                            ResponseResult::Err(
+                                format!("Error running `brew --repository`: {}", String::from_utf8_lossy(&output.stderr)).into()
+                            )
+                        )
+                    )
+                }
+            }))
+    }
+
     fn installed(&self, handle: &Handle, name: &str, _: &Os) -> ExecutableResult {
         let handle = handle.clone();
         let name = name.to_owned();
@@ -52,7 +79,101 @@ impl PackageProvider for Homebrew {
                     future::ok(
                         Message::WithoutBody(
                             ResponseResult::Err(
-                                format!("Error running `brew list installed`: {}", String::from_utf8_lossy(&output.stderr))
+                                format!("Error running `brew list installed`: {}", String::from_utf8_lossy(&output.stderr)).into()
+                            )
+                        )
+                    )
+                }
+            }))
+    }
+
+    fn installed_many(&self, handle: &Handle, names: &[&str], _: &Os) -> ExecutableResult {
+        let handle = handle.clone();
+        let names: Vec<String> = names.iter().map(|n| (*n).to_owned()).collect();
+
+        Box::new(process::Command::new("brew")
+            .arg("list")
+            .output_async(&handle)
+            .chain_err(|| "Could not get installed packages")
+            .map(move |output| {
+                if output.status.success() {
+                    let stdout = String::from_utf8_lossy(&output.stdout);
+                    let installed = names.iter()
+                        .filter(|n| stdout.split_whitespace().any(|f| f == n.as_str()))
+                        .cloned()
+                        .collect();
+                    Message::WithoutBody(
+                        ResponseResult::Ok(
+                            Response::List(installed)))
+                } else {
+                    Message::WithoutBody(
+                        ResponseResult::Err(
+                            format!("Error running `brew list`: {}", String::from_utf8_lossy(&output.stderr)).into()))
+                }
+            }))
+    }
+
+    fn version(&self, handle: &Handle, name: &str, version: Option<&str>, _: &Os) -> ExecutableResult {
+        let handle = handle.clone();
+        // A pinned package is installed as its versioned formula, which can
+        // sit alongside the unversioned one, so only look at that.
+        let pinned = match version {
+            Some(v) => match versioned_formula(name, v) {
+                Ok(formula) => Some(formula),
+                Err(e) => return Box::new(future::ok(
+                    Message::WithoutBody(
+                        ResponseResult::Err(
+                            e.into())))),
+            },
+            None => None,
+        };
+        let name = name.to_owned();
+
+        Box::new(process::Command::new("brew")
+            .args(["list", "--versions"])
+            .output_async(&handle)
+            .chain_err(|| "Could not get installed package version")
+            .and_then(move |output| {
+                if output.status.success() {
+                    let stdout = String::from_utf8_lossy(&output.stdout);
+                    future::ok(
+                        Message::WithoutBody(
+                            ResponseResult::Ok(
+                                Response::Version(
+                                    listed_version(&stdout, &name, pinned.as_deref())))))
+                } else {
+                    future::ok(
+                        Message::WithoutBody(
+                            ResponseResult::Err(
+                                format!("Error running `brew list --versions`: {}", String::from_utf8_lossy(&output.stderr)).into()
+                            )
+                        )
+                    )
+                }
+            }))
+    }
+
+    fn is_outdated(&self, handle: &Handle, name: &str, _: &Os) -> ExecutableResult {
+        let handle = handle.clone();
+        let name = name.to_owned();
+
+        Box::new(process::Command::new("brew")
+            .args(["outdated", "--quiet"])
+            .output_async(&handle)
+            .chain_err(|| "Could not check for package upgrades")
+            .and_then(move |output| {
+                if output.status.success() {
+                    let stdout = String::from_utf8_lossy(&output.stdout);
+                    future::ok(
+                        Message::WithoutBody(
+                            ResponseResult::Ok(
+                                Response::Bool(
+                                    stdout.lines().any(|l| l.trim() == name)))))
+                } else {
+                    future::ok(
+                        Message::WithoutBody(
+                            ResponseResult::Err(
+                                format!("Error running `brew outdated`: {}", String::from_utf8_lossy(&output.stderr)).into()
                             )
                         )
                     )
@@ -60,15 +181,37 @@ impl PackageProvider for Homebrew {
             }))
     }
 
-    fn install(&self, handle: &Handle, name: &str) -> ExecutableResult {
+    fn install(&self, handle: &Handle, name: &str, version: Option<&str>) -> ExecutableResult {
         let cmd = match factory() {
             Ok(c) => c,
             Err(e) => return Box::new(future::ok(
                 Message::WithoutBody(
                     ResponseResult::Err(
-                        format!("{}", e.display_chain()))))),
+                        e.into())))),
         };
-        cmd.exec(handle, &["brew", "install", name])
+        match version {
+            Some(v) => match versioned_formula(name, v) {
+                Ok(formula) => cmd.exec(handle, &["brew", "install", "--", &formula]),
+                Err(e) => Box::new(future::ok(
+                    Message::WithoutBody(
+                        ResponseResult::Err(
+                            e.into())))),
+            },
+            None => cmd.exec(handle, &["brew", "install", "--", name]),
+        }
+    }
+
+    fn install_many(&self, handle: &Handle, names: &[&str]) -> ExecutableResult {
+        let cmd = match factory() {
+            Ok(c) => c,
+            Err(e) => return Box::new(future::ok(
+                Message::WithoutBody(
+                    ResponseResult::Err(
+                        e.into())))),
+        };
+        let mut args = vec!["brew", "install", "--"];
+        args.extend_from_slice(names);
+        cmd.exec(handle, &args)
     }
 
     fn uninstall(&self, handle: &Handle, name: &str) -> ExecutableResult {
@@ -77,8 +220,88 @@ impl PackageProvider for Homebrew {
             Err(e) => return Box::new(future::ok(
                 Message::WithoutBody(
                     ResponseResult::Err(
-                        format!("{}", e.display_chain()))))),
+                        e.into())))),
+        };
+        cmd.exec(handle, &["brew", "uninstall", "--", name])
+    }
+
+    fn uninstall_many(&self, handle: &Handle, names: &[&str]) -> ExecutableResult {
+        let cmd = match factory() {
+            Ok(c) => c,
+            Err(e) => return Box::new(future::ok(
+                Message::WithoutBody(
+                    ResponseResult::Err(
+                        e.into())))),
+        };
+        let mut args = vec!["brew", "uninstall", "--"];
+        args.extend_from_slice(names);
+        cmd.exec(handle, &args)
+    }
+
+    fn update_cache(&self, handle: &Handle) -> ExecutableResult {
+        let cmd = match factory() {
+            Ok(c) => c,
+            Err(e) => return Box::new(future::ok(
+                Message::WithoutBody(
+                    ResponseResult::Err(
+                        e.into())))),
+        };
+        cmd.exec(handle, &["brew", "update"])
+    }
+
+    fn upgrade(&self, handle: &Handle, name: &str) -> ExecutableResult {
+        let cmd = match factory() {
+            Ok(c) => c,
+            Err(e) => return Box::new(future::ok(
+                Message::WithoutBody(
+                    ResponseResult::Err(
+                        e.into())))),
         };
-        cmd.exec(handle, &["brew", "uninstall", name])
+        cmd.exec(handle, &["brew", "upgrade", "--", name])
+    }
+}
+
+// Find a formula's version in the output of `brew list --versions`, where
+// each line is a formula followed by its installed versions. Pinned versions
+// are installed as versioned formulae, e.g. "nginx@1.18", so when no pin is
+// given, fall back to those if the unversioned formula isn't installed.
+fn listed_version(stdout: &str, name: &str, pinned: Option<&str>) -> Option<String> {
+    let versioned = format!("{}@", name);
+    let mut formulae: Vec<Vec<&str>> = stdout.lines()
+        .map(|l| l.split_whitespace().collect())
+        .filter(|f: &Vec<&str>| f.len() > 1 && match pinned {
+            Some(formula) => f[0] == formula,
+            None => f[0] == name || f[0].starts_with(&versioned),
+        })
+        .collect();
+    formulae.sort_by_key(|f| f[0] != name);
+    formulae.first().map(|f| f[f.len() - 1].to_owned())
+}
+
+// Homebrew only has specific versions of some packages, which are separate
+// formulae, e.g. "nginx@1.18" for `1.18.*`. These only ever go down to the
+// major or minor version, so exact versions can't be installed.
+fn versioned_formula(name: &str, version: &str) -> Result<String> {
+    let prefix = version.trim_right_matches(".*");
+    let parts: Vec<&str> = prefix.split('.').collect();
+    if prefix == version || parts.len() > 2 || parts.iter().any(|p| p.is_empty() || !p.chars().all(|c| c.is_ascii_digit())) {
+        return Err(format!("Homebrew can only pin a major or major.minor version, e.g. `1.18.*`, not `{}`", version).into());
+    }
+
+    Ok(format!("{}@{}", name, prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::listed_version;
+
+    #[test]
+    fn test_listed_version() {
+        let stdout = "nginx 1.25.3\nnginx@1.18 1.18.0 1.18.1\nopenssl@3 3.2.0\n";
+        assert_eq!(listed_version(stdout, "nginx", None), Some("1.25.3".into()));
+        assert_eq!(listed_version(stdout, "nginx", Some("nginx@1.18")), Some("1.18.1".into()));
+        assert_eq!(listed_version(stdout, "nginx", Some("nginx@1.16")), None);
+        assert_eq!(listed_version(stdout, "openssl", None), Some("3.2.0".into()));
+        assert_eq!(listed_version(stdout, "curl", None), None);
     }
 }
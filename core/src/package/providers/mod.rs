@@ -15,6 +15,9 @@ mod yum;
 
 use errors::*;
 use remote::ExecutableResult;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 pub use self::apt::Apt;
 pub use self::dnf::Dnf;
 pub use self::homebrew::Homebrew;
@@ -37,9 +40,17 @@ pub enum Provider {
 
 pub trait PackageProvider {
     fn available() -> Result<bool> where Self: Sized;
+    fn cache_age(&self, &Handle) -> ExecutableResult;
     fn installed(&self, &Handle, &str, &Os) -> ExecutableResult;
-    fn install(&self, &Handle, &str) -> ExecutableResult;
+    fn installed_many(&self, &Handle, &[&str], &Os) -> ExecutableResult;
+    fn version(&self, &Handle, &str, Option<&str>, &Os) -> ExecutableResult;
+    fn is_outdated(&self, &Handle, &str, &Os) -> ExecutableResult;
+    fn install(&self, &Handle, &str, Option<&str>) -> ExecutableResult;
+    fn install_many(&self, &Handle, &[&str]) -> ExecutableResult;
     fn uninstall(&self, &Handle, &str) -> ExecutableResult;
+    fn uninstall_many(&self, &Handle, &[&str]) -> ExecutableResult;
+    fn update_cache(&self, &Handle) -> ExecutableResult;
+    fn upgrade(&self, &Handle, &str) -> ExecutableResult;
 }
 
 #[doc(hidden)]
@@ -62,6 +73,48 @@ pub fn factory() -> Result<Box<PackageProvider>> {
     else if Yum::available()? {
         Ok(Box::new(Yum))
     } else {
-        Err(ErrorKind::ProviderUnavailable("Package").into())
+        Err(ErrorKind::ProviderUnavailable("Package".into()).into())
+    }
+}
+
+// How long ago the most recently modified of `paths` was modified, or
+// `None` if none of them exist. Symlinks aren't followed, as some package
+// managers switch a symlink to mark an update.
+fn modified_age<P: AsRef<Path>>(paths: &[P]) -> Option<Duration> {
+    paths.iter()
+        .filter_map(|p| fs::symlink_metadata(p).and_then(|m| m.modified()).ok())
+        .max()
+        .map(|t| SystemTime::now().duration_since(t).unwrap_or(Duration::from_secs(0)))
+}
+
+// Find files under `dir` called `name`, looking no more than `depth`
+// directories deep. `name` may contain a `*` wildcard.
+fn find_files<P: AsRef<Path>>(dir: P, name: &str, depth: usize) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    let entries = match fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(_) => return found,
+    };
+
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if path.is_dir() {
+            if depth > 1 {
+                found.extend(find_files(&path, name, depth - 1));
+            }
+        } else if name_matches(name, &entry.file_name().to_string_lossy()) {
+            found.push(path);
+        }
+    }
+
+    found
+}
+
+fn name_matches(pattern: &str, name: &str) -> bool {
+    match pattern.find('*') {
+        Some(i) => name.len() >= pattern.len() - 1 &&
+            name.starts_with(&pattern[..i]) &&
+            name.ends_with(&pattern[i + 1..]),
+        None => pattern == name,
     }
 }
@@ -5,12 +5,11 @@
 // modified, or distributed except according to those terms.
 
 use command::factory;
-use error_chain::ChainedError;
 use errors::*;
 use futures::{future, Future};
 use remote::{ExecutableResult, Response, ResponseResult};
-use std::process;
-use super::PackageProvider;
+use std::{env, process};
+use super::{modified_age, PackageProvider};
 use telemetry::Os;
 use tokio_core::reactor::Handle;
 use tokio_process::CommandExt;
@@ -27,27 +26,113 @@ impl PackageProvider for Nix {
             .success())
     }
 
+    fn cache_age(&self, _: &Handle) -> ExecutableResult {
+        // Updating channels creates a new generation of the user's channels
+        // profile.
+        let user = env::var("USER").unwrap_or_else(|_| "root".into());
+        let age = modified_age(&[format!("/nix/var/nix/profiles/per-user/{}/channels", user)]);
+        Box::new(future::ok(
+            Message::WithoutBody(
+                ResponseResult::Ok(
+                    Response::Age(age)))))
+    }
+
     fn installed(&self, handle: &Handle, name: &str, _: &Os) -> ExecutableResult {
         let handle = handle.clone();
         let name = name.to_owned();
 
         Box::new(process::Command::new("nix-env")
-            .args(&["--install", "--dry-run", &name])
+            .args(["--query", "--installed"])
             .output_async(&handle)
             .chain_err(|| "Could not check if package is installed")
-            .and_then(move |output| {
+            .map(move |output| {
+                if output.status.success() {
+                    let stdout = String::from_utf8_lossy(&output.stdout);
+                    Message::WithoutBody(
+                        ResponseResult::Ok(
+                            Response::Bool(
+                                stdout.lines().any(|l| is_derivation_of(l, &name)))))
+                } else {
+                    Message::WithoutBody(
+                        ResponseResult::Err(
+                            format!("Error running `nix-env --query`: {}", String::from_utf8_lossy(&output.stderr)).into()))
+                }
+            }))
+    }
+
+    fn installed_many(&self, handle: &Handle, names: &[&str], _: &Os) -> ExecutableResult {
+        let handle = handle.clone();
+        let names: Vec<String> = names.iter().map(|n| (*n).to_owned()).collect();
+
+        Box::new(process::Command::new("nix-env")
+            .args(["--query", "--installed"])
+            .output_async(&handle)
+            .chain_err(|| "Could not get installed packages")
+            .map(move |output| {
                 if output.status.success() {
                     let stdout = String::from_utf8_lossy(&output.stdout);
+                    let installed = names.iter()
+                        .filter(|n| stdout.lines().any(|l| is_derivation_of(l, n)))
+                        .cloned()
+                        .collect();
+                    Message::WithoutBody(
+                        ResponseResult::Ok(
+                            Response::List(installed)))
+                } else {
+                    Message::WithoutBody(
+                        ResponseResult::Err(
+                            format!("Error running `nix-env --query`: {}", String::from_utf8_lossy(&output.stderr)).into()))
+                }
+            }))
+    }
+
+    fn version(&self, handle: &Handle, name: &str, _: Option<&str>, _: &Os) -> ExecutableResult {
+        let handle = handle.clone();
+        let name = name.to_owned();
+
+        Box::new(process::Command::new("nix-env")
+            .args(["--query", "--installed", "--", &name])
+            .output_async(&handle)
+            .chain_err(|| "Could not get installed package version")
+            .map(move |output| {
+                // nix-env prints the derivation name, e.g. "nginx-1.18.0",
+                // and fails if no installed derivation matches.
+                let prefix = format!("{}-", name);
+                let version = if output.status.success() {
+                    String::from_utf8_lossy(&output.stdout).lines()
+                        .rfind(|l| l.starts_with(&prefix))
+                        .map(|l| l[prefix.len()..].into())
+                } else {
+                    None
+                };
+                Message::WithoutBody(
+                    ResponseResult::Ok(
+                        Response::Version(version)))
+            }))
+    }
+
+    fn is_outdated(&self, handle: &Handle, name: &str, _: &Os) -> ExecutableResult {
+        let handle = handle.clone();
+        let name = name.to_owned();
+
+        Box::new(process::Command::new("nix-env")
+            .args(["--upgrade", "--dry-run", "--", &name])
+            .output_async(&handle)
+            .chain_err(|| "Could not check for package upgrades")
+            .and_then(move |output| {
+                if output.status.success() {
+                    // Nix says what it would upgrade on stderr
+                    let stderr = String::from_utf8_lossy(&output.stderr);
                     future::ok(
                         Message::WithoutBody(
                             ResponseResult::Ok(
                                 Response::Bool(
-                                    !stdout.contains("these paths will be fetched")))))
+                                    stderr.contains("upgrading")))))
                 } else {
                     future::ok(
                         Message::WithoutBody(
                             ResponseResult::Err(
-                                format!("Error running `nix-env --install --dry-run {}`: {}", name, String::from_utf8_lossy(&output.stderr))
+                                format!("Error running `nix-env --upgrade --dry-run {}`: {}", name, String::from_utf8_lossy(&output.stderr)).into()
                             )
                         )
                     )
@@ -55,15 +140,37 @@ impl PackageProvider for Nix {
             }))
     }
 
-    fn install(&self, handle: &Handle, name: &str) -> ExecutableResult {
+    fn install(&self, handle: &Handle, name: &str, version: Option<&str>) -> ExecutableResult {
         let cmd = match factory() {
             Ok(c) => c,
             Err(e) => return Box::new(future::ok(
                 Message::WithoutBody(
                     ResponseResult::Err(
-                        format!("{}", e.display_chain()))))),
+                        e.into())))),
         };
-        cmd.exec(handle, &["nix-env", "--install", name])
+        match version {
+            // Nix selects derivations by exact name, so it can't do
+            // constraints like `1.18.*`.
+            Some(v) if v.contains('*') => Box::new(future::ok(
+                Message::WithoutBody(
+                    ResponseResult::Err(
+                        format!("Nix can only install exact package versions, not \"{}\"", v).into())))),
+            Some(v) => cmd.exec(handle, &["nix-env", "--install", "--", &format!("{}-{}", name, v)]),
+            None => cmd.exec(handle, &["nix-env", "--install", "--", name]),
+        }
+    }
+
+    fn install_many(&self, handle: &Handle, names: &[&str]) -> ExecutableResult {
+        let cmd = match factory() {
+            Ok(c) => c,
+            Err(e) => return Box::new(future::ok(
+                Message::WithoutBody(
+                    ResponseResult::Err(
+                        e.into())))),
+        };
+        let mut args = vec!["nix-env", "--install", "--"];
+        args.extend_from_slice(names);
+        cmd.exec(handle, &args)
     }
 
     fn uninstall(&self, handle: &Handle, name: &str) -> ExecutableResult {
@@ -72,8 +179,50 @@ impl PackageProvider for Nix {
             Err(e) => return Box::new(future::ok(
                 Message::WithoutBody(
                     ResponseResult::Err(
-                        format!("{}", e.display_chain()))))),
+                        e.into())))),
+        };
+        cmd.exec(handle, &["nix-env", "--uninstall", "--", name])
+    }
+
+    fn uninstall_many(&self, handle: &Handle, names: &[&str]) -> ExecutableResult {
+        let cmd = match factory() {
+            Ok(c) => c,
+            Err(e) => return Box::new(future::ok(
+                Message::WithoutBody(
+                    ResponseResult::Err(
+                        e.into())))),
         };
-        cmd.exec(handle, &["nix-env", "--uninstall", name])
+        let mut args = vec!["nix-env", "--uninstall", "--"];
+        args.extend_from_slice(names);
+        cmd.exec(handle, &args)
     }
+
+    fn update_cache(&self, handle: &Handle) -> ExecutableResult {
+        let cmd = match factory() {
+            Ok(c) => c,
+            Err(e) => return Box::new(future::ok(
+                Message::WithoutBody(
+                    ResponseResult::Err(
+                        e.into())))),
+        };
+        cmd.exec(handle, &["nix-channel", "--update"])
+    }
+
+    fn upgrade(&self, handle: &Handle, name: &str) -> ExecutableResult {
+        let cmd = match factory() {
+            Ok(c) => c,
+            Err(e) => return Box::new(future::ok(
+                Message::WithoutBody(
+                    ResponseResult::Err(
+                        e.into())))),
+        };
+        cmd.exec(handle, &["nix-env", "--upgrade", "--", name])
+    }
+}
+
+// Whether `line` from `nix-env --query` is a derivation of package `name`.
+// nix-env lists derivation names, e.g. "nginx-1.18.0".
+fn is_derivation_of(line: &str, name: &str) -> bool {
+    line.starts_with(name) && line[name.len()..].starts_with('-') &&
+        line[name.len() + 1..].starts_with(|c: char| c.is_ascii_digit())
 }
@@ -5,12 +5,11 @@
 // modified, or distributed except according to those terms.
 
 use command::factory;
-use error_chain::ChainedError;
 use errors::*;
 use futures::{future, Future};
 use remote::{ExecutableResult, Response, ResponseResult};
 use std::process;
-use super::PackageProvider;
+use super::{find_files, modified_age, PackageProvider};
 use telemetry::Os;
 use tokio_core::reactor::Handle;
 use tokio_process::CommandExt;
@@ -27,12 +26,23 @@ impl PackageProvider for Pkg {
             .success())
     }
 
+    fn cache_age(&self, _: &Handle) -> ExecutableResult {
+        // Each repository has its own catalogue, e.g.
+        // /var/db/pkg/repo-FreeBSD.sqlite
+        let catalogues = find_files("/var/db/pkg", "repo-*.sqlite", 1);
+        let age = modified_age(&catalogues);
+        Box::new(future::ok(
+            Message::WithoutBody(
+                ResponseResult::Ok(
+                    Response::Age(age)))))
+    }
+
     fn installed(&self, handle: &Handle, name: &str, _: &Os) -> ExecutableResult {
         let handle = handle.clone();
         let name = name.to_owned();
 
         Box::new(process::Command::new("pkg")
-            .args(&["query", "\"%n\"", &name])
+            .args(["query", "\"%n\"", "--", &name])
             .output_async(&handle)
             .chain_err(|| "Could not get installed packages")
             .and_then(move |output| {
@@ -44,15 +54,106 @@ impl PackageProvider for Pkg {
             }))
     }
 
-    fn install(&self, handle: &Handle, name: &str) -> ExecutableResult {
+    fn installed_many(&self, handle: &Handle, names: &[&str], _: &Os) -> ExecutableResult {
+        let handle = handle.clone();
+        let names: Vec<String> = names.iter().map(|n| (*n).to_owned()).collect();
+        let mut args = vec!["query".to_owned(), "%n".into(), "--".into()];
+        args.extend(names.iter().cloned());
+
+        Box::new(process::Command::new("pkg")
+            .args(&args)
+            .output_async(&handle)
+            .chain_err(|| "Could not get installed packages")
+            .map(move |output| {
+                // pkg fails if some of the packages aren't installed, but
+                // still lists the rest.
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                let installed = names.iter()
+                    .filter(|n| stdout.lines().any(|l| l == n.as_str()))
+                    .cloned()
+                    .collect();
+                Message::WithoutBody(
+                    ResponseResult::Ok(
+                        Response::List(installed)))
+            }))
+    }
+
+    fn version(&self, handle: &Handle, name: &str, _: Option<&str>, _: &Os) -> ExecutableResult {
+        let handle = handle.clone();
+
+        Box::new(process::Command::new("pkg")
+            .args(["query", "%v", "--", name])
+            .output_async(&handle)
+            .chain_err(|| "Could not get installed package version")
+            .map(|output| {
+                // pkg fails if the package isn't installed
+                let version = if output.status.success() {
+                    Some(String::from_utf8_lossy(&output.stdout).trim().into())
+                } else {
+                    None
+                };
+                Message::WithoutBody(
+                    ResponseResult::Ok(
+                        Response::Version(version)))
+            }))
+    }
+
+    fn is_outdated(&self, handle: &Handle, name: &str, _: &Os) -> ExecutableResult {
+        let handle = handle.clone();
+        let name = name.to_owned();
+
+        Box::new(process::Command::new("pkg")
+            .args(["version", "--remote", "--like", "<", "--match", &name])
+            .output_async(&handle)
+            .chain_err(|| "Could not check for package upgrades")
+            .and_then(move |output| {
+                if output.status.success() {
+                    // pkg lists the package only if the repository has a
+                    // newer version.
+                    future::ok(
+                        Message::WithoutBody(
+                            ResponseResult::Ok(
+                                Response::Bool(
+                                    !output.stdout.is_empty()))))
+                } else {
+                    future::ok(
+                        Message::WithoutBody(
+                            ResponseResult::Err(
+                                format!("Error running `pkg version`: {}", String::from_utf8_lossy(&output.stderr)).into()
+                            )
+                        )
+                    )
+                }
+            }))
+    }
+
+    fn install(&self, handle: &Handle, name: &str, version: Option<&str>) -> ExecutableResult {
+        let cmd = match factory() {
+            Ok(c) => c,
+            Err(e) => return Box::new(future::ok(
+                Message::WithoutBody(
+                    ResponseResult::Err(
+                        e.into())))),
+        };
+        match version {
+            // Match the version as a glob, so that constraints like
+            // `1.18.*` work.
+            Some(v) => cmd.exec(handle, &["pkg", "install", "-y", "-g", "--", &format!("{}-{}", name, v)]),
+            None => cmd.exec(handle, &["pkg", "install", "-y", "--", name]),
+        }
+    }
+
+    fn install_many(&self, handle: &Handle, names: &[&str]) -> ExecutableResult {
         let cmd = match factory() {
             Ok(c) => c,
             Err(e) => return Box::new(future::ok(
                 Message::WithoutBody(
                     ResponseResult::Err(
-                        format!("{}", e.display_chain()))))),
+                        e.into())))),
         };
-        cmd.exec(handle, &["pkg", "install", "-y", name])
+        let mut args = vec!["pkg", "install", "-y", "--"];
+        args.extend_from_slice(names);
+        cmd.exec(handle, &args)
     }
 
     fn uninstall(&self, handle: &Handle, name: &str) -> ExecutableResult {
@@ -61,8 +162,43 @@ impl PackageProvider for Pkg {
             Err(e) => return Box::new(future::ok(
                 Message::WithoutBody(
                     ResponseResult::Err(
-                        format!("{}", e.display_chain()))))),
+                        e.into())))),
+        };
+        cmd.exec(handle, &["pkg", "delete", "-y", "--", name])
+    }
+
+    fn uninstall_many(&self, handle: &Handle, names: &[&str]) -> ExecutableResult {
+        let cmd = match factory() {
+            Ok(c) => c,
+            Err(e) => return Box::new(future::ok(
+                Message::WithoutBody(
+                    ResponseResult::Err(
+                        e.into())))),
+        };
+        let mut args = vec!["pkg", "delete", "-y", "--"];
+        args.extend_from_slice(names);
+        cmd.exec(handle, &args)
+    }
+
+    fn update_cache(&self, handle: &Handle) -> ExecutableResult {
+        let cmd = match factory() {
+            Ok(c) => c,
+            Err(e) => return Box::new(future::ok(
+                Message::WithoutBody(
+                    ResponseResult::Err(
+                        e.into())))),
+        };
+        cmd.exec(handle, &["pkg", "update"])
+    }
+
+    fn upgrade(&self, handle: &Handle, name: &str) -> ExecutableResult {
+        let cmd = match factory() {
+            Ok(c) => c,
+            Err(e) => return Box::new(future::ok(
+                Message::WithoutBody(
+                    ResponseResult::Err(
+                        e.into())))),
         };
-        cmd.exec(handle, &["pkg", "delete", "-y", name])
+        cmd.exec(handle, &["pkg", "upgrade", "-y", "--", name])
     }
 }
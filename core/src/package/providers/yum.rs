@@ -5,13 +5,12 @@
 // modified, or distributed except according to those terms.
 
 use command::factory;
-use error_chain::ChainedError;
 use errors::*;
 use futures::{future, Future};
 use regex::Regex;
 use remote::{ExecutableResult, Response, ResponseResult};
 use std::process;
-use super::PackageProvider;
+use super::{find_files, modified_age, PackageProvider};
 use telemetry::Os;
 use tokio_core::reactor::Handle;
 use tokio_process::CommandExt;
@@ -29,6 +28,17 @@ impl PackageProvider for Yum {
             .success())
     }
 
+    fn cache_age(&self, _: &Handle) -> ExecutableResult {
+        // Each repository has its own cookie, e.g.
+        // /var/cache/yum/x86_64/7/base/cachecookie
+        let cookies = find_files("/var/cache/yum", "cachecookie", 4);
+        let age = modified_age(&cookies);
+        Box::new(future::ok(
+            Message::WithoutBody(
+                ResponseResult::Ok(
+                    Response::Age(age)))))
+    }
+
     fn installed(&self, handle: &Handle, name: &str, os: &Os) -> ExecutableResult {
         let handle = handle.clone();
         let name = name.to_owned();
@@ -54,7 +64,7 @@ impl PackageProvider for Yum {
                     future::ok(
                         Message::WithoutBody(
                             ResponseResult::Err(
-                                format!("Error running `yum list installed`: {}", String::from_utf8_lossy(&output.stderr))
+                                format!("Error running `yum list installed`: {}", String::from_utf8_lossy(&output.stderr)).into()
                             )
                         )
                     )
@@ -62,15 +72,112 @@ impl PackageProvider for Yum {
             }))
     }
 
-    fn install(&self, handle: &Handle, name: &str) -> ExecutableResult {
+    fn installed_many(&self, handle: &Handle, names: &[&str], _: &Os) -> ExecutableResult {
+        let handle = handle.clone();
+        let names: Vec<String> = names.iter().map(|n| (*n).to_owned()).collect();
+        let mut args = vec!["--query".to_owned(), "--queryformat".into(), "%{NAME}\\n".into(), "--".into()];
+        args.extend(names.iter().cloned());
+
+        Box::new(process::Command::new("rpm")
+            .args(&args)
+            .output_async(&handle)
+            .chain_err(|| "Could not get installed packages")
+            .map(move |output| {
+                // rpm fails if some of the packages aren't installed, and
+                // says so in its output, which we can ignore.
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                let installed = names.iter()
+                    .filter(|n| stdout.lines().any(|l| l == n.as_str()))
+                    .cloned()
+                    .collect();
+                Message::WithoutBody(
+                    ResponseResult::Ok(
+                        Response::List(installed)))
+            }))
+    }
+
+    fn version(&self, handle: &Handle, name: &str, _: Option<&str>, _: &Os) -> ExecutableResult {
+        let handle = handle.clone();
+
+        Box::new(process::Command::new("rpm")
+            .args(["--query", "--queryformat", "%{VERSION}-%{RELEASE}\\n", "--", name])
+            .output_async(&handle)
+            .chain_err(|| "Could not get installed package version")
+            .map(|output| {
+                // rpm fails if the package isn't installed. Some packages,
+                // e.g. kernels, can have several versions installed, in
+                // which case we report the newest.
+                let version = if output.status.success() {
+                    String::from_utf8_lossy(&output.stdout).lines().last().map(|v| v.into())
+                } else {
+                    None
+                };
+                Message::WithoutBody(
+                    ResponseResult::Ok(
+                        Response::Version(version)))
+            }))
+    }
+
+    fn is_outdated(&self, handle: &Handle, name: &str, _: &Os) -> ExecutableResult {
+        let handle = handle.clone();
+        let name = name.to_owned();
+
+        Box::new(process::Command::new("yum")
+            .args(["check-update", "--quiet", "--", &name])
+            .output_async(&handle)
+            .chain_err(|| "Could not check for package upgrades")
+            .and_then(move |output| {
+                // Yum exits with 100 if there are updates, and 0 if there
+                // aren't.
+                match output.status.code() {
+                    Some(100) => future::ok(
+                        Message::WithoutBody(
+                            ResponseResult::Ok(
+                                Response::Bool(
+                                    true)))),
+                    Some(0) => future::ok(
+                        Message::WithoutBody(
+                            ResponseResult::Ok(
+                                Response::Bool(
+                                    false)))),
+                    _ => future::ok(
+                        Message::WithoutBody(
+                            ResponseResult::Err(
+                                format!("Error running `yum check-update {}`: {}", name, String::from_utf8_lossy(&output.stderr)).into()
+                            )
+                        )
+                    ),
+                }
+            }))
+    }
+
+    fn install(&self, handle: &Handle, name: &str, version: Option<&str>) -> ExecutableResult {
+        let cmd = match factory() {
+            Ok(c) => c,
+            Err(e) => return Box::new(future::ok(
+                Message::WithoutBody(
+                    ResponseResult::Err(
+                        e.into())))),
+        };
+        match version {
+            // Unlike Dnf, Yum won't install a version older than the one
+            // that's installed, so try downgrading first.
+            Some(v) => cmd.exec(handle, &["/bin/sh", "-c", "yum -y downgrade -- \"$1\" || yum -y install -- \"$1\"", "sh", &format!("{}-{}", name, v)]),
+            None => cmd.exec(handle, &["yum", "-y", "install", "--", name]),
+        }
+    }
+
+    fn install_many(&self, handle: &Handle, names: &[&str]) -> ExecutableResult {
         let cmd = match factory() {
             Ok(c) => c,
             Err(e) => return Box::new(future::ok(
                 Message::WithoutBody(
                     ResponseResult::Err(
-                        format!("{}", e.display_chain()))))),
+                        e.into())))),
         };
-        cmd.exec(handle, &["yum", "-y", "install", name])
+        let mut args = vec!["yum", "-y", "install", "--"];
+        args.extend_from_slice(names);
+        cmd.exec(handle, &args)
     }
 
     fn uninstall(&self, handle: &Handle, name: &str) -> ExecutableResult {
@@ -79,8 +186,43 @@ impl PackageProvider for Yum {
             Err(e) => return Box::new(future::ok(
                 Message::WithoutBody(
                     ResponseResult::Err(
-                        format!("{}", e.display_chain()))))),
+                        e.into())))),
+        };
+        cmd.exec(handle, &["yum", "-y", "remove", "--", name])
+    }
+
+    fn uninstall_many(&self, handle: &Handle, names: &[&str]) -> ExecutableResult {
+        let cmd = match factory() {
+            Ok(c) => c,
+            Err(e) => return Box::new(future::ok(
+                Message::WithoutBody(
+                    ResponseResult::Err(
+                        e.into())))),
+        };
+        let mut args = vec!["yum", "-y", "remove", "--"];
+        args.extend_from_slice(names);
+        cmd.exec(handle, &args)
+    }
+
+    fn update_cache(&self, handle: &Handle) -> ExecutableResult {
+        let cmd = match factory() {
+            Ok(c) => c,
+            Err(e) => return Box::new(future::ok(
+                Message::WithoutBody(
+                    ResponseResult::Err(
+                        e.into())))),
+        };
+        cmd.exec(handle, &["yum", "makecache"])
+    }
+
+    fn upgrade(&self, handle: &Handle, name: &str) -> ExecutableResult {
+        let cmd = match factory() {
+            Ok(c) => c,
+            Err(e) => return Box::new(future::ok(
+                Message::WithoutBody(
+                    ResponseResult::Err(
+                        e.into())))),
         };
-        cmd.exec(handle, &["yum", "-y", "remove", name])
+        cmd.exec(handle, &["yum", "-y", "update", "--", name])
     }
 }
@@ -0,0 +1,166 @@
+// Copyright 2015-2017 Intecture Developers.
+//
+// Licensed under the Mozilla Public License 2.0 <LICENSE or
+// https://www.tldrlegal.com/l/mpl-2.0>. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+use command::CommandStatus;
+use errors::*;
+use futures::{future, Future};
+use host::Host;
+use remote::{Request, Response};
+use super::{Provider, MAX_OUTPUT};
+
+/// Represents a group of packages to be managed together for a host.
+///
+/// Managing packages one at a time means a round trip to the host and a
+/// package manager run for each one. A `PackageSet` checks all of its
+/// packages in one query, then installs or uninstalls only those that need
+/// it, in one transaction.
+///
+///# Example
+///
+/// Install some packages and print the ones that were installed.
+///
+///```no_run
+///extern crate futures;
+///extern crate intecture_api;
+///extern crate tokio_core;
+///
+///use futures::{future, Future};
+///use intecture_api::errors::*;
+///use intecture_api::prelude::*;
+///use tokio_core::reactor::Core;
+///
+///# fn main() {
+///let mut core = Core::new().unwrap();
+///let handle = core.handle();
+///
+///let host = Local::new(&handle).wait().unwrap();
+///
+///let packages = PackageSet::new(&host, &["nginx", "php-fpm", "redis-server"]);
+///let result = packages.install().and_then(|status| {
+///    match status {
+///        // We're installing the packages that were missing
+///        Some((names, status)) => Box::new(status.result().unwrap()
+///            .map(move |_| println!("Installed {}", names.join(", ")))) as Box<Future<Item = _, Error = Error>>,
+///
+///        // All of the packages are already installed
+///        None => Box::new(future::ok(())),
+///    }
+///});
+///
+///core.run(result).unwrap();
+///# }
+///```
+pub struct PackageSet<H: Host> {
+    host: H,
+    provider: Option<Provider>,
+    names: Vec<String>,
+}
+
+impl<H: Host + 'static> PackageSet<H> {
+    /// Create a new `PackageSet` with the default [`Provider`](enum.Provider.html).
+    pub fn new(host: &H, names: &[&str]) -> PackageSet<H> {
+        PackageSet {
+            host: host.clone(),
+            provider: None,
+            names: names.iter().map(|n| (*n).to_owned()).collect(),
+        }
+    }
+
+    /// Create a new `PackageSet` with the specified [`Provider`](enum.Provider.html).
+    pub fn with_provider(host: &H, provider: Provider, names: &[&str]) -> PackageSet<H> {
+        PackageSet {
+            host: host.clone(),
+            provider: Some(provider),
+            names: names.iter().map(|n| (*n).to_owned()).collect(),
+        }
+    }
+
+    /// Get the names of the packages in this set that are installed.
+    pub fn installed(&self) -> Box<Future<Item = Vec<String>, Error = Error>> {
+        let request = Request::PackageInstalledMany(self.provider, self.names.clone());
+        if !self.host.supports(request.name()) {
+            return Box::new(future::err(ErrorKind::Unsupported(request.name().into()).into()));
+        }
+
+        Box::new(self.host.request(request)
+            .chain_err(|| ErrorKind::Request { endpoint: "PackageSet".into(), func: "installed".into() })
+            .map(|msg| {
+                match msg.into_inner() {
+                    Response::List(names) => names,
+                    _ => unreachable!(),
+                }
+            }))
+    }
+
+    /// Install the packages that aren't already installed.
+    ///
+    ///## Idempotence
+    ///
+    /// This function is idempotent, which is represented by the type
+    /// `Future<Item = Option<..>, ...>`. Thus if it returns `Option::None`
+    /// then all of the packages are already installed, and if it returns
+    /// `Option::Some` then Intecture is attempting to install the rest.
+    ///
+    /// If this fn returns `Option::Some<..>`, the nested tuple will hold the
+    /// names of the packages being installed, and the status of the package
+    /// manager installing them. Under the hood this reuses the `Command`
+    /// endpoint, so see [`Command` docs](../command/struct.Command.html) for
+    /// detailed usage.
+    pub fn install(&self) -> Box<Future<Item = Option<(Vec<String>, CommandStatus)>, Error = Error>> {
+        let names = self.names.clone();
+        self.change(move |installed| {
+            names.into_iter().filter(|n| !installed.contains(n)).collect()
+        }, Request::PackageInstallMany, "install")
+    }
+
+    /// Uninstall the packages that are installed.
+    ///
+    ///## Idempotence
+    ///
+    /// This function is idempotent, which is represented by the type
+    /// `Future<Item = Option<..>, ...>`. Thus if it returns `Option::None`
+    /// then none of the packages are installed, and if it returns
+    /// `Option::Some` then Intecture is attempting to uninstall those that
+    /// are.
+    ///
+    /// If this fn returns `Option::Some<..>`, the nested tuple will hold the
+    /// names of the packages being uninstalled, and the status of the package
+    /// manager uninstalling them. Under the hood this reuses the `Command`
+    /// endpoint, so see [`Command` docs](../command/struct.Command.html) for
+    /// detailed usage.
+    pub fn uninstall(&self) -> Box<Future<Item = Option<(Vec<String>, CommandStatus)>, Error = Error>> {
+        self.change(|installed| installed, Request::PackageUninstallMany, "uninstall")
+    }
+
+    // Work out which packages need changing from those that are installed,
+    // then send `request` for just those.
+    fn change<F, R>(&self, needed: F, request: R, func: &'static str) -> Box<Future<Item = Option<(Vec<String>, CommandStatus)>, Error = Error>>
+        where F: FnOnce(Vec<String>) -> Vec<String> + 'static,
+              R: FnOnce(Option<Provider>, Vec<String>) -> Request + 'static
+    {
+        let host = self.host.clone();
+        let provider = self.provider;
+
+        Box::new(self.installed()
+            .and_then(move |installed| {
+                let names = needed(installed);
+                if names.is_empty() {
+                    return Box::new(future::ok(None)) as Box<Future<Item = _, Error = Error>>;
+                }
+
+                let request = request(provider, names.clone());
+                if !host.supports(request.name()) {
+                    return Box::new(future::err(ErrorKind::Unsupported(request.name().into()).into()));
+                }
+
+                Box::new(host.request(request)
+                    .chain_err(move || ErrorKind::Request { endpoint: "PackageSet".into(), func: func.into() })
+                    .and_then(move |msg| {
+                        CommandStatus::new(&host, msg).map(|status| Some((names, status.limit_output(MAX_OUTPUT))))
+                    }))
+            }))
+    }
+}
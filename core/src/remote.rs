@@ -8,22 +8,84 @@
 
 use command;
 use errors::*;
+use errors::serializable;
 use futures::{future, Future};
 use host::Host;
+use host::local::Local;
 use package;
+use repository;
 use service;
 use std::io;
+use std::time::Duration;
 use telemetry::{self, Telemetry};
 use tokio_proto::streaming::{Body, Message};
 
+/// Version of the wire protocol spoken by this crate. Bump this whenever the
+/// protocol changes, e.g. when changing the shape of a `Request`. New
+/// requests don't need a new version, as they're advertised in
+/// `CAPABILITIES` instead.
+pub const PROTOCOL_VERSION: u32 = 14;
+
+/// Oldest version of the wire protocol that this crate can still talk to.
+/// Hosts and agents can talk to each other so long as their supported
+/// versions overlap. Raise this to `PROTOCOL_VERSION` whenever a change would
+/// break existing peers.
+pub const MIN_PROTOCOL_VERSION: u32 = 14;
+
+/// Capabilities advertised to peers during the handshake. Adding a new
+/// `Request` doesn't break older peers, so long as we check that the peer
+/// supports it before sending it.
+pub const CAPABILITIES: &'static [&'static str] = &[
+    "CommandCancel",
+    "CommandExec",
+    "CommandResize",
+    "CommandSignal",
+    "PackageCacheAge",
+    "PackageCacheUpdate",
+    "PackageInstalled",
+    "PackageInstalledMany",
+    "PackageInstall",
+    "PackageInstallMany",
+    "PackageOutdated",
+    "PackageUninstall",
+    "PackageUninstallMany",
+    "PackageUpgrade",
+    "PackageVersion",
+    "RepositoryAdd",
+    "RepositoryConfigured",
+    "RepositoryExists",
+    "RepositoryRemove",
+    "ServiceAction",
+    "ServiceDisable",
+    "ServiceEnable",
+    "ServiceEnabled",
+    "ServiceRunning",
+    "TelemetryLoad",
+];
+
 pub type ExecutableResult = Box<Future<Item = Message<ResponseResult, Body<Vec<u8>, io::Error>>, Error = Error>>;
 
 #[derive(Serialize, Deserialize)]
 pub enum Request {
-    CommandExec(Option<command::Provider>, Vec<String>),
+    CommandCancel(Option<command::Provider>, u32),
+    CommandExec(Option<command::Provider>, Vec<String>, command::ExecOptions),
+    CommandResize(Option<command::Provider>, u32, command::WindowSize),
+    CommandSignal(Option<command::Provider>, u32, command::Signal),
+    PackageCacheAge(Option<package::Provider>),
+    PackageCacheUpdate(Option<package::Provider>),
     PackageInstalled(Option<package::Provider>, String),
-    PackageInstall(Option<package::Provider>, String),
+    PackageInstalledMany(Option<package::Provider>, Vec<String>),
+    PackageInstall(Option<package::Provider>, String, Option<String>),
+    PackageInstallMany(Option<package::Provider>, Vec<String>),
+    PackageOutdated(Option<package::Provider>, String),
     PackageUninstall(Option<package::Provider>, String),
+    PackageUninstallMany(Option<package::Provider>, Vec<String>),
+    PackageUpgrade(Option<package::Provider>, String),
+    PackageVersion(Option<package::Provider>, String, Option<String>),
+    RepositoryAdd(Option<repository::Provider>, String, String, Option<String>),
+    RepositoryConfigured(Option<repository::Provider>, String, String, Option<String>),
+    RepositoryExists(Option<repository::Provider>, String),
+    RepositoryRemove(Option<repository::Provider>, String),
     ServiceAction(Option<service::Provider>, String, String),
     ServiceDisable(Option<service::Provider>, String),
     ServiceEnable(Option<service::Provider>, String),
@@ -34,108 +96,332 @@ pub enum Request {
 
 #[derive(Serialize, Deserialize)]
 pub enum Response {
+    Age(Option<Duration>),
     Bool(bool),
+    List(Vec<String>),
     Null,
-    TelemetryLoad(telemetry::serializable::Telemetry),
+    Pid(u32),
+    TelemetryLoad(Box<telemetry::serializable::Telemetry>),
+    Version(Option<String>),
 }
 
 #[derive(Serialize, Deserialize)]
 pub enum ResponseResult {
     Ok(Response),
-    Err(String),
+    Err(serializable::Error),
+    Denied(String),
+}
+
+impl Request {
+    /// The name of this request, e.g. "PackageInstall".
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Request::CommandCancel(..) => "CommandCancel",
+            Request::CommandExec(..) => "CommandExec",
+            Request::CommandResize(..) => "CommandResize",
+            Request::CommandSignal(..) => "CommandSignal",
+            Request::PackageCacheAge(..) => "PackageCacheAge",
+            Request::PackageCacheUpdate(..) => "PackageCacheUpdate",
+            Request::PackageInstalled(..) => "PackageInstalled",
+            Request::PackageInstalledMany(..) => "PackageInstalledMany",
+            Request::PackageInstall(..) => "PackageInstall",
+            Request::PackageInstallMany(..) => "PackageInstallMany",
+            Request::PackageOutdated(..) => "PackageOutdated",
+            Request::PackageUninstall(..) => "PackageUninstall",
+            Request::PackageUninstallMany(..) => "PackageUninstallMany",
+            Request::PackageUpgrade(..) => "PackageUpgrade",
+            Request::PackageVersion(..) => "PackageVersion",
+            Request::RepositoryAdd(..) => "RepositoryAdd",
+            Request::RepositoryConfigured(..) => "RepositoryConfigured",
+            Request::RepositoryExists(..) => "RepositoryExists",
+            Request::RepositoryRemove(..) => "RepositoryRemove",
+            Request::ServiceAction(..) => "ServiceAction",
+            Request::ServiceDisable(..) => "ServiceDisable",
+            Request::ServiceEnable(..) => "ServiceEnable",
+            Request::ServiceEnabled(..) => "ServiceEnabled",
+            Request::ServiceRunning(..) => "ServiceRunning",
+            Request::TelemetryLoad => "TelemetryLoad",
+        }
+    }
+
+    /// The things this request acts upon, e.g. the package name for
+    /// `PackageInstall`. Most requests have one subject, though batch
+    /// requests like `PackageInstallMany` have one per package, and some
+    /// have none. `CommandExec` has none either, as a command's arguments
+    /// can't be squashed into a string without losing where each one ends.
+    pub fn subjects(&self) -> Vec<&str> {
+        match *self {
+            Request::PackageInstalledMany(_, ref names) |
+            Request::PackageInstallMany(_, ref names) |
+            Request::PackageUninstallMany(_, ref names) => names.iter().map(|n| &**n).collect(),
+            _ => self.subject().into_iter().collect(),
+        }
+    }
+
+    // The subject of a request that has at most one
+    fn subject(&self) -> Option<&str> {
+        match *self {
+            Request::PackageInstalled(_, ref name) |
+            Request::PackageInstall(_, ref name, _) |
+            Request::PackageOutdated(_, ref name) |
+            Request::PackageUninstall(_, ref name) |
+            Request::PackageUpgrade(_, ref name) |
+            Request::PackageVersion(_, ref name, _) |
+            Request::RepositoryAdd(_, ref name, _, _) |
+            Request::RepositoryConfigured(_, ref name, _, _) |
+            Request::RepositoryExists(_, ref name) |
+            Request::RepositoryRemove(_, ref name) |
+            Request::ServiceAction(_, ref name, _) |
+            Request::ServiceDisable(_, ref name) |
+            Request::ServiceEnable(_, ref name) |
+            Request::ServiceEnabled(_, ref name) |
+            Request::ServiceRunning(_, ref name) => Some(name),
+            Request::CommandCancel(..) |
+            Request::CommandExec(..) |
+            Request::CommandResize(..) |
+            Request::CommandSignal(..) |
+            Request::PackageCacheAge(..) |
+            Request::PackageCacheUpdate(..) |
+            Request::PackageInstalledMany(..) |
+            Request::PackageInstallMany(..) |
+            Request::PackageUninstallMany(..) |
+            Request::TelemetryLoad => None,
+        }
+    }
 }
 
 pub trait Executable {
-    fn exec<H: Host>(self, &H) -> ExecutableResult;
+    fn exec(self, &Local) -> ExecutableResult;
 }
 
 impl Executable for Request {
-    fn exec<H: Host>(self, host: &H) -> ExecutableResult {
-        match self {
-            Request::CommandExec(provider, cmd) => {
-                let provider = match provider {
-                    Some(command::Provider::Generic) => Box::new(command::Generic),
-                    None => match command::factory() {
-                        Ok(p) => p,
-                        Err(e) => return Box::new(future::err(e)),
-                    },
-                };
-                let args: Vec<&str> = cmd.iter().map(|a| &**a).collect();
-                provider.exec(host.handle(), &args)
-            }
-
-            Request::PackageInstalled(provider, name) => {
-                let provider = match get_package_provider(provider) {
-                    Ok(p) => p,
-                    Err(e) => return Box::new(future::err(e)),
-                };
-                provider.installed(host.handle(), &name, &host.telemetry().os)
-            }
-
-            Request::PackageInstall(provider, name) => {
-                let provider = match get_package_provider(provider) {
-                    Ok(p) => p,
-                    Err(e) => return Box::new(future::err(e)),
-                };
-                provider.install(host.handle(), &name)
-            }
-
-            Request::PackageUninstall(provider, name) => {
-                let provider = match get_package_provider(provider) {
-                    Ok(p) => p,
-                    Err(e) => return Box::new(future::err(e)),
-                };
-                provider.uninstall(host.handle(), &name)
-            }
-
-            Request::ServiceAction(provider, name, action) => {
-                let provider = match get_service_provider(&host.telemetry(), provider) {
-                    Ok(p) => p,
-                    Err(e) => return Box::new(future::err(e)),
-                };
-                provider.action(host.handle(), &name, &action)
-            }
-
-            Request::ServiceEnabled(provider, name) => {
-                let provider = match get_service_provider(&host.telemetry(), provider) {
-                    Ok(p) => p,
-                    Err(e) => return Box::new(future::err(e)),
-                };
-                provider.enabled(host.handle(), &name)
-            }
-
-            Request::ServiceRunning(provider, name) => {
-                let provider = match get_service_provider(&host.telemetry(), provider) {
-                    Ok(p) => p,
-                    Err(e) => return Box::new(future::err(e)),
-                };
-                provider.running(host.handle(), &name)
-            }
-
-            Request::ServiceEnable(provider, name) => {
-                let provider = match get_service_provider(&host.telemetry(), provider) {
-                    Ok(p) => p,
-                    Err(e) => return Box::new(future::err(e)),
-                };
-                provider.enable(host.handle(), &name)
-            }
-
-            Request::ServiceDisable(provider, name) => {
-                let provider = match get_service_provider(&host.telemetry(), provider) {
-                    Ok(p) => p,
-                    Err(e) => return Box::new(future::err(e)),
-                };
-                provider.disable(host.handle(), &name)
-            }
-
-            Request::TelemetryLoad => {
-                let provider = match telemetry::factory() {
-                    Ok(p) => p,
-                    Err(e) => return Box::new(future::err(e)),
-                };
-                provider.load()
-            }
+    fn exec(self, host: &Local) -> ExecutableResult {
+        Message::WithoutBody(self).exec(host)
+    }
+}
+
+// Requests may carry a body, e.g. stdin for `CommandExec`.
+impl Executable for Message<Request, Body<Vec<u8>, io::Error>> {
+    fn exec(self, host: &Local) -> ExecutableResult {
+        // Commands belong to the session that started them
+        host.processes().enter(|| dispatch(self, host))
+    }
+}
+
+fn dispatch(mut msg: Message<Request, Body<Vec<u8>, io::Error>>, host: &Local) -> ExecutableResult {
+    let body = msg.take_body();
+
+    match msg.into_inner() {
+        Request::CommandCancel(provider, pid) => {
+            let provider = match get_command_provider(provider) {
+                Ok(p) => p,
+                Err(e) => return Box::new(future::err(e)),
+            };
+            provider.cancel(host.handle(), pid)
+        }
+
+        Request::CommandExec(provider, cmd, options) => {
+            let provider = match get_command_provider(provider) {
+                Ok(p) => p,
+                Err(e) => return Box::new(future::err(e)),
+            };
+            let args: Vec<&str> = cmd.iter().map(|a| &**a).collect();
+            provider.exec_with(host.handle(), &args, &options, body)
+        }
+
+        Request::CommandResize(provider, pid, size) => {
+            let provider = match get_command_provider(provider) {
+                Ok(p) => p,
+                Err(e) => return Box::new(future::err(e)),
+            };
+            provider.resize(host.handle(), pid, size)
+        }
+
+        Request::CommandSignal(provider, pid, signal) => {
+            let provider = match get_command_provider(provider) {
+                Ok(p) => p,
+                Err(e) => return Box::new(future::err(e)),
+            };
+            provider.signal(host.handle(), pid, signal)
+        }
+
+        Request::PackageCacheAge(provider) => {
+            let provider = match get_package_provider(provider) {
+                Ok(p) => p,
+                Err(e) => return Box::new(future::err(e)),
+            };
+            provider.cache_age(host.handle())
+        }
+
+        Request::PackageCacheUpdate(provider) => {
+            let provider = match get_package_provider(provider) {
+                Ok(p) => p,
+                Err(e) => return Box::new(future::err(e)),
+            };
+            provider.update_cache(host.handle())
+        }
+
+        Request::PackageInstalled(provider, name) => {
+            let provider = match get_package_provider(provider) {
+                Ok(p) => p,
+                Err(e) => return Box::new(future::err(e)),
+            };
+            provider.installed(host.handle(), &name, &host.telemetry().os)
+        }
+
+        Request::PackageInstalledMany(provider, names) => {
+            let provider = match get_package_provider(provider) {
+                Ok(p) => p,
+                Err(e) => return Box::new(future::err(e)),
+            };
+            let names: Vec<&str> = names.iter().map(|n| &**n).collect();
+            provider.installed_many(host.handle(), &names, &host.telemetry().os)
+        }
+
+        Request::PackageInstall(provider, name, version) => {
+            let provider = match get_package_provider(provider) {
+                Ok(p) => p,
+                Err(e) => return Box::new(future::err(e)),
+            };
+            provider.install(host.handle(), &name, version.as_deref())
+        }
+
+        Request::PackageInstallMany(provider, names) => {
+            let provider = match get_package_provider(provider) {
+                Ok(p) => p,
+                Err(e) => return Box::new(future::err(e)),
+            };
+            let names: Vec<&str> = names.iter().map(|n| &**n).collect();
+            provider.install_many(host.handle(), &names)
         }
+
+        Request::PackageOutdated(provider, name) => {
+            let provider = match get_package_provider(provider) {
+                Ok(p) => p,
+                Err(e) => return Box::new(future::err(e)),
+            };
+            provider.is_outdated(host.handle(), &name, &host.telemetry().os)
+        }
+
+        Request::PackageUninstall(provider, name) => {
+            let provider = match get_package_provider(provider) {
+                Ok(p) => p,
+                Err(e) => return Box::new(future::err(e)),
+            };
+            provider.uninstall(host.handle(), &name)
+        }
+
+        Request::PackageUninstallMany(provider, names) => {
+            let provider = match get_package_provider(provider) {
+                Ok(p) => p,
+                Err(e) => return Box::new(future::err(e)),
+            };
+            let names: Vec<&str> = names.iter().map(|n| &**n).collect();
+            provider.uninstall_many(host.handle(), &names)
+        }
+
+        Request::PackageUpgrade(provider, name) => {
+            let provider = match get_package_provider(provider) {
+                Ok(p) => p,
+                Err(e) => return Box::new(future::err(e)),
+            };
+            provider.upgrade(host.handle(), &name)
+        }
+
+        Request::PackageVersion(provider, name, version) => {
+            let provider = match get_package_provider(provider) {
+                Ok(p) => p,
+                Err(e) => return Box::new(future::err(e)),
+            };
+            provider.version(host.handle(), &name, version.as_deref(), &host.telemetry().os)
+        }
+
+        Request::RepositoryAdd(provider, name, source, key) => {
+            let provider = match get_repository_provider(provider) {
+                Ok(p) => p,
+                Err(e) => return Box::new(future::err(e)),
+            };
+            provider.add(host.handle(), &name, &source, key.as_deref())
+        }
+
+        Request::RepositoryConfigured(provider, name, source, key) => {
+            let provider = match get_repository_provider(provider) {
+                Ok(p) => p,
+                Err(e) => return Box::new(future::err(e)),
+            };
+            provider.configured(host.handle(), &name, &source, key.as_deref())
+        }
+
+        Request::RepositoryExists(provider, name) => {
+            let provider = match get_repository_provider(provider) {
+                Ok(p) => p,
+                Err(e) => return Box::new(future::err(e)),
+            };
+            provider.exists(host.handle(), &name)
+        }
+
+        Request::RepositoryRemove(provider, name) => {
+            let provider = match get_repository_provider(provider) {
+                Ok(p) => p,
+                Err(e) => return Box::new(future::err(e)),
+            };
+            provider.remove(host.handle(), &name)
+        }
+
+        Request::ServiceAction(provider, name, action) => {
+            let provider = match get_service_provider(host.telemetry(), provider) {
+                Ok(p) => p,
+                Err(e) => return Box::new(future::err(e)),
+            };
+            provider.action(host.handle(), &name, &action)
+        }
+
+        Request::ServiceEnabled(provider, name) => {
+            let provider = match get_service_provider(host.telemetry(), provider) {
+                Ok(p) => p,
+                Err(e) => return Box::new(future::err(e)),
+            };
+            provider.enabled(host.handle(), &name)
+        }
+
+        Request::ServiceRunning(provider, name) => {
+            let provider = match get_service_provider(host.telemetry(), provider) {
+                Ok(p) => p,
+                Err(e) => return Box::new(future::err(e)),
+            };
+            provider.running(host.handle(), &name)
+        }
+
+        Request::ServiceEnable(provider, name) => {
+            let provider = match get_service_provider(host.telemetry(), provider) {
+                Ok(p) => p,
+                Err(e) => return Box::new(future::err(e)),
+            };
+            provider.enable(host.handle(), &name)
+        }
+
+        Request::ServiceDisable(provider, name) => {
+            let provider = match get_service_provider(host.telemetry(), provider) {
+                Ok(p) => p,
+                Err(e) => return Box::new(future::err(e)),
+            };
+            provider.disable(host.handle(), &name)
+        }
+
+        Request::TelemetryLoad => {
+            let provider = match telemetry::factory() {
+                Ok(p) => p,
+                Err(e) => return Box::new(future::err(e)),
+            };
+            provider.load()
+        }
+    }
+}
+
+fn get_command_provider(name: Option<command::Provider>) -> Result<Box<command::CommandProvider>> {
+    match name {
+        Some(command::Provider::Generic) => Ok(Box::new(command::Generic)),
+        None => command::factory(),
     }
 }
 
@@ -151,6 +437,17 @@ fn get_package_provider(name: Option<package::Provider>) -> Result<Box<package::
     }
 }
 
+fn get_repository_provider(name: Option<repository::Provider>) -> Result<Box<repository::RepositoryProvider>> {
+    match name {
+        Some(repository::Provider::Apt) => Ok(Box::new(repository::Apt)),
+        Some(repository::Provider::Dnf) => Ok(Box::new(repository::Dnf)),
+        Some(repository::Provider::Homebrew) => Ok(Box::new(repository::Homebrew)),
+        Some(repository::Provider::Pkg) => Ok(Box::new(repository::Pkg)),
+        Some(repository::Provider::Yum) => Ok(Box::new(repository::Yum)),
+        None => repository::factory(),
+    }
+}
+
 fn get_service_provider(telemetry: &Telemetry, name: Option<service::Provider>) -> Result<Box<service::ServiceProvider>> {
     match name {
         Some(service::Provider::Debian) => Ok(Box::new(service::Debian)),
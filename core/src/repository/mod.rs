@@ -0,0 +1,225 @@
+// Copyright 2015-2017 Intecture Developers.
+//
+// Licensed under the Mozilla Public License 2.0 <LICENSE or
+// https://www.tldrlegal.com/l/mpl-2.0>. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Endpoint for managing package repositories.
+//!
+//! A repository is represented by the `Repository` struct, which is
+//! idempotent. This means you can execute it repeatedly and it'll only run as
+//! needed.
+
+mod providers;
+
+use command::CommandStatus;
+use errors::*;
+use futures::{future, Future};
+use host::Host;
+use remote::{Request, Response};
+#[doc(hidden)]
+pub use self::providers::{factory, RepositoryProvider, Apt, Dnf, Homebrew, Pkg, Yum};
+pub use self::providers::Provider;
+
+/// Represents a package repository to be managed for a host.
+///
+/// A repository has a name, a source and optionally a signing key. The
+/// source is given in the provider's own format:
+///
+/// - Apt takes a sources list entry, e.g.
+///   `deb https://download.docker.com/linux/ubuntu focal stable`. This is
+///   written to `/etc/apt/sources.list.d/<name>.list`.
+/// - Dnf and Yum take a base URL, which is written to
+///   `/etc/yum.repos.d/<name>.repo`.
+/// - Pkg takes a repository URL, which is written to
+///   `/usr/local/etc/pkg/repos/<name>.conf`.
+/// - Homebrew takes the tap's Git URL, and the name is the tap's name, e.g.
+///   `user/repo`.
+///
+///# Example
+///
+/// Add the PostgreSQL repository, then install a package from it.
+///
+///```no_run
+///extern crate futures;
+///extern crate intecture_api;
+///extern crate tokio_core;
+///
+///use futures::{future, Future};
+///use intecture_api::errors::*;
+///use intecture_api::prelude::*;
+///use intecture_api::repository::Provider;
+///use tokio_core::reactor::Core;
+///
+///# fn main() {
+///let mut core = Core::new().unwrap();
+///let handle = core.handle();
+///
+///let host = Local::new(&handle).wait().unwrap();
+///
+///let repo = Repository::with_provider(&host, Provider::Apt, "pgdg",
+///        "deb http://apt.postgresql.org/pub/repos/apt focal-pgdg main")
+///    .key("https://www.postgresql.org/media/keys/ACCC4CF8.asc");
+///let cache = PackageCache::new(&host);
+///let postgres = Package::new(&host, "postgresql-13");
+///
+///let result = repo.add()
+///    .and_then(|status| match status {
+///        // We're adding the repository, so refresh the package index once
+///        // it's done
+///        Some(status) => Box::new(status.result().unwrap()
+///            .and_then(move |_| cache.update(None))
+///            .and_then(|status| status.unwrap().result().unwrap())
+///            .map(|_| ())) as Box<Future<Item = _, Error = Error>>,
+///
+///        // This repository is already configured
+///        None => Box::new(future::ok(())),
+///    })
+///    .and_then(move |_| postgres.install());
+///
+///core.run(result).unwrap();
+///# }
+///```
+pub struct Repository<H: Host> {
+    host: H,
+    provider: Option<Provider>,
+    name: String,
+    source: String,
+    key: Option<String>,
+}
+
+impl<H: Host + 'static> Repository<H> {
+    /// Create a new `Repository` with the default [`Provider`](enum.Provider.html).
+    pub fn new(host: &H, name: &str, source: &str) -> Repository<H> {
+        Repository {
+            host: host.clone(),
+            provider: None,
+            name: name.into(),
+            source: source.into(),
+            key: None,
+        }
+    }
+
+    /// Create a new `Repository` with the specified [`Provider`](enum.Provider.html).
+    pub fn with_provider(host: &H, provider: Provider, name: &str, source: &str) -> Repository<H> {
+        Repository {
+            host: host.clone(),
+            provider: Some(provider),
+            name: name.into(),
+            source: source.into(),
+            key: None,
+        }
+    }
+
+    /// Sign the repository with the key at `url`.
+    ///
+    /// Apt and Pkg download the key when the repository is added, and Apt
+    /// limits the repository to packages signed by it. Keys ending in `.gpg`
+    /// are treated as binary keys, and anything else as ASCII armored. Dnf
+    /// and Yum import the key the first time they install a package from
+    /// the repository. Homebrew taps can't be signed.
+    pub fn key(mut self, url: &str) -> Repository<H> {
+        self.key = Some(url.into());
+        self
+    }
+
+    /// Check if the repository exists, regardless of how it's configured. A
+    /// signing key left behind by a repository counts as the repository
+    /// existing, so that `remove()` will clean it up.
+    pub fn exists(&self) -> Box<Future<Item = bool, Error = Error>> {
+        let request = Request::RepositoryExists(self.provider, self.name.clone());
+        if !self.host.supports(request.name()) {
+            return Box::new(future::err(ErrorKind::Unsupported(request.name().into()).into()));
+        }
+
+        Box::new(self.host.request(request)
+            .chain_err(|| ErrorKind::Request { endpoint: "Repository".into(), func: "exists".into() })
+            .map(|msg| {
+                match msg.into_inner() {
+                    Response::Bool(b) => b,
+                    _ => unreachable!(),
+                }
+            }))
+    }
+
+    /// Add the repository, or update it if it's configured differently.
+    ///
+    /// This doesn't refresh the package index, so you'll probably want to do
+    /// that with [`PackageCache`](../package/struct.PackageCache.html)
+    /// before installing packages from the repository.
+    ///
+    ///## Idempotence
+    ///
+    /// This function is idempotent, which is represented by the type
+    /// `Future<Item = Option<..>, ...>`. Thus if it returns `Option::None`
+    /// then the repository is already configured with this source and key,
+    /// and if it returns `Option::Some` then Intecture is attempting to add
+    /// it.
+    ///
+    /// If this fn returns `Option::Some<..>`, the
+    /// [`CommandStatus`](../command/struct.CommandStatus.html) will hold
+    /// handles to the live output and the result of the addition.
+    pub fn add(&self) -> Box<Future<Item = Option<CommandStatus>, Error = Error>> {
+        let request = Request::RepositoryConfigured(self.provider, self.name.clone(), self.source.clone(), self.key.clone());
+        if !self.host.supports(request.name()) {
+            return Box::new(future::err(ErrorKind::Unsupported(request.name().into()).into()));
+        }
+
+        let host = self.host.clone();
+        let provider = self.provider;
+        let name = self.name.clone();
+        let source = self.source.clone();
+        let key = self.key.clone();
+
+        Box::new(self.host.request(request)
+            .chain_err(|| ErrorKind::Request { endpoint: "Repository".into(), func: "add".into() })
+            .map(|msg| {
+                match msg.into_inner() {
+                    Response::Bool(b) => b,
+                    _ => unreachable!(),
+                }
+            })
+            .and_then(move |configured| {
+                if configured {
+                    Box::new(future::ok(None)) as Box<Future<Item = _, Error = Error>>
+                } else {
+                    Box::new(host.request(Request::RepositoryAdd(provider, name, source, key))
+                        .chain_err(|| ErrorKind::Request { endpoint: "Repository".into(), func: "add".into() })
+                        .and_then(move |msg| {
+                            CommandStatus::new(&host, msg).map(Some)
+                        }))
+                }
+            }))
+    }
+
+    /// Remove the repository, along with its signing key.
+    ///
+    ///## Idempotence
+    ///
+    /// This function is idempotent, which is represented by the type
+    /// `Future<Item = Option<..>, ...>`. Thus if it returns `Option::None`
+    /// then the repository doesn't exist, and if it returns `Option::Some`
+    /// then Intecture is attempting to remove it.
+    ///
+    /// If this fn returns `Option::Some<..>`, the
+    /// [`CommandStatus`](../command/struct.CommandStatus.html) will hold
+    /// handles to the live output and the result of the removal.
+    pub fn remove(&self) -> Box<Future<Item = Option<CommandStatus>, Error = Error>> {
+        let host = self.host.clone();
+        let provider = self.provider;
+        let name = self.name.clone();
+
+        Box::new(self.exists()
+            .and_then(move |exists| {
+                if exists {
+                    Box::new(host.request(Request::RepositoryRemove(provider, name))
+                        .chain_err(|| ErrorKind::Request { endpoint: "Repository".into(), func: "remove".into() })
+                        .and_then(move |msg| {
+                            CommandStatus::new(&host, msg).map(Some)
+                        }))
+                } else {
+                    Box::new(future::ok(None)) as Box<Future<Item = _, Error = Error>>
+                }
+            }))
+    }
+}
@@ -0,0 +1,170 @@
+// Copyright 2015-2017 Intecture Developers.
+//
+// Licensed under the Mozilla Public License 2.0 <LICENSE or
+// https://www.tldrlegal.com/l/mpl-2.0>. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+use errors::*;
+use futures::future;
+use remote::{ExecutableResult, Response, ResponseResult};
+use std::path::{Path, PathBuf};
+use std::process;
+use super::{check_value, config_path, is_configured, key_source, remove_config, write_config, RepositoryProvider};
+use tokio_core::reactor::Handle;
+use tokio_proto::streaming::Message;
+
+const SOURCES_DIR: &'static str = "/etc/apt/sources.list.d";
+const KEYRINGS_DIR: &'static str = "/etc/apt/keyrings";
+
+pub struct Apt;
+
+impl RepositoryProvider for Apt {
+    fn available() -> Result<bool> {
+        Ok(process::Command::new("/usr/bin/type")
+            .arg("apt-get")
+            .status()
+            .chain_err(|| "Could not determine provider availability")?
+            .success())
+    }
+
+    fn exists(&self, _: &Handle, name: &str) -> ExecutableResult {
+        let paths = match repo_files(name) {
+            Ok(p) => p,
+            Err(e) => return Box::new(future::err(e)),
+        };
+        Box::new(future::ok(
+            Message::WithoutBody(
+                ResponseResult::Ok(
+                    Response::Bool(paths.iter().any(|p| p.exists()))))))
+    }
+
+    fn configured(&self, _: &Handle, name: &str, source: &str, key: Option<&str>) -> ExecutableResult {
+        let (path, contents, key_path) = match sources_list(name, source, key) {
+            Ok(s) => s,
+            Err(e) => return Box::new(future::err(e)),
+        };
+        let configured = is_configured(&path, &contents, key_path.as_deref());
+        Box::new(future::ok(
+            Message::WithoutBody(
+                ResponseResult::Ok(
+                    Response::Bool(configured)))))
+    }
+
+    fn add(&self, handle: &Handle, name: &str, source: &str, key: Option<&str>) -> ExecutableResult {
+        let (path, contents, key_path) = match sources_list(name, source, key) {
+            Ok(s) => s,
+            Err(e) => return Box::new(future::err(e)),
+        };
+        let key = match (key, key_path.as_ref()) {
+            (Some(url), Some(path)) => Some((url, &**path)),
+            _ => None,
+        };
+        write_config(handle, &path, &contents, key)
+    }
+
+    fn remove(&self, handle: &Handle, name: &str) -> ExecutableResult {
+        let paths = match repo_files(name) {
+            Ok(p) => p,
+            Err(e) => return Box::new(future::err(e)),
+        };
+        remove_config(handle, &paths)
+    }
+}
+
+// Every file that a repository may have left behind, including keys that
+// outlived their sources list.
+fn repo_files(name: &str) -> Result<Vec<PathBuf>> {
+    Ok(vec![
+        config_path(SOURCES_DIR, name, "list")?,
+        config_path(KEYRINGS_DIR, name, "asc")?,
+        config_path(KEYRINGS_DIR, name, "gpg")?,
+    ])
+}
+
+// The path and contents of a repository's sources list, and the path of its
+// signing key, if it has one.
+fn sources_list(name: &str, source: &str, key: Option<&str>) -> Result<(PathBuf, String, Option<PathBuf>)> {
+    let path = config_path(SOURCES_DIR, name, "list")?;
+
+    // Apt decides whether a key is ASCII armored by its extension
+    let key_path = match key {
+        Some(url) if url.ends_with(".gpg") => Some(config_path(KEYRINGS_DIR, name, "gpg")?),
+        Some(_) => Some(config_path(KEYRINGS_DIR, name, "asc")?),
+        None => None,
+    };
+
+    let mut contents = match key {
+        Some(url) => key_source(url)?,
+        None => String::new(),
+    };
+    for line in source.lines() {
+        check_value("source", line)?;
+        match key_path {
+            Some(ref key) => contents.push_str(&signed_by(line.trim(), key)),
+            None => contents.push_str(line.trim()),
+        }
+        contents.push('\n');
+    }
+
+    Ok((path, contents, key_path))
+}
+
+// Restrict a source to packages signed by `key`, unless it already says
+// which key it's signed by.
+fn signed_by(line: &str, key: &Path) -> String {
+    if !line.starts_with("deb") || line.contains("signed-by=") {
+        return line.into();
+    }
+
+    let mut parts = line.splitn(2, ' ');
+    let kind = parts.next().unwrap_or("");
+    let rest = parts.next().unwrap_or("").trim_left();
+    match rest.strip_prefix('[') {
+        Some(options) => format!("{} [signed-by={} {}", kind, key.display(), options.trim_left()),
+        None => format!("{} [signed-by={}] {}", kind, key.display(), rest),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use super::{signed_by, sources_list};
+
+    #[test]
+    fn test_signed_by() {
+        let key = Path::new("/etc/apt/keyrings/docker.asc");
+        assert_eq!(signed_by("deb https://example.com focal stable", key),
+                   "deb [signed-by=/etc/apt/keyrings/docker.asc] https://example.com focal stable");
+        assert_eq!(signed_by("deb [arch=amd64] https://example.com focal stable", key),
+                   "deb [signed-by=/etc/apt/keyrings/docker.asc arch=amd64] https://example.com focal stable");
+        assert_eq!(signed_by("deb-src https://example.com focal stable", key),
+                   "deb-src [signed-by=/etc/apt/keyrings/docker.asc] https://example.com focal stable");
+        // Sources that already have a key are left alone, as are comments
+        assert_eq!(signed_by("deb [signed-by=/other.gpg] https://example.com focal stable", key),
+                   "deb [signed-by=/other.gpg] https://example.com focal stable");
+        assert_eq!(signed_by("# deb https://example.com focal stable", key),
+                   "# deb https://example.com focal stable");
+    }
+
+    #[test]
+    fn test_sources_list() {
+        let (path, contents, key) = sources_list("docker", "deb https://example.com focal stable", None).unwrap();
+        assert_eq!(path, PathBuf::from("/etc/apt/sources.list.d/docker.list"));
+        assert_eq!(contents, "deb https://example.com focal stable\n");
+        assert!(key.is_none());
+
+        let (_, contents, key) = sources_list("docker", " deb https://example.com focal stable\ndeb-src https://example.com focal stable",
+                                              Some("https://example.com/key.gpg")).unwrap();
+        assert_eq!(key, Some(PathBuf::from("/etc/apt/keyrings/docker.gpg")));
+        assert_eq!(contents, "# Signing key: https://example.com/key.gpg\n\
+                              deb [signed-by=/etc/apt/keyrings/docker.gpg] https://example.com focal stable\n\
+                              deb-src [signed-by=/etc/apt/keyrings/docker.gpg] https://example.com focal stable\n");
+
+        let (_, _, key) = sources_list("docker", "deb https://example.com focal stable", Some("https://example.com/key")).unwrap();
+        assert_eq!(key, Some(PathBuf::from("/etc/apt/keyrings/docker.asc")));
+
+        assert!(sources_list("../docker", "deb https://example.com focal stable", None).is_err());
+        assert!(sources_list("docker", "deb https://example.com focal stable\r", None).is_err());
+        assert!(sources_list("docker", "deb https://example.com focal stable", Some("https://example.com/key\ndeb https://example.com/evil focal main")).is_err());
+    }
+}
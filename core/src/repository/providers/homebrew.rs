@@ -0,0 +1,129 @@
+// Copyright 2015-2017 Intecture Developers.
+//
+// Licensed under the Mozilla Public License 2.0 <LICENSE or
+// https://www.tldrlegal.com/l/mpl-2.0>. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+use command::factory;
+use errors::*;
+use futures::{future, Future};
+use remote::{ExecutableResult, Response, ResponseResult};
+use serde_json::{self, Value};
+use std::process;
+use super::RepositoryProvider;
+use tokio_core::reactor::Handle;
+use tokio_process::CommandExt;
+use tokio_proto::streaming::Message;
+
+pub struct Homebrew;
+
+impl RepositoryProvider for Homebrew {
+    fn available() -> Result<bool> {
+        Ok(process::Command::new("/usr/bin/type")
+            .arg("brew")
+            .status()
+            .chain_err(|| "Could not determine provider availability")?
+            .success())
+    }
+
+    fn exists(&self, handle: &Handle, name: &str) -> ExecutableResult {
+        let handle = handle.clone();
+        let name = name.to_lowercase();
+
+        Box::new(process::Command::new("brew")
+            .arg("tap")
+            .output_async(&handle)
+            .chain_err(|| "Could not get taps")
+            .and_then(move |output| {
+                if output.status.success() {
+                    let stdout = String::from_utf8_lossy(&output.stdout);
+                    future::ok(
+                        Message::WithoutBody(
+                            ResponseResult::Ok(
+                                Response::Bool(
+                                    stdout.lines().any(|l| l.trim() == name)))))
+                } else {
+                    future::ok(
+                        Message::WithoutBody(
+                            ResponseResult::Err(
+                                format!("Error running `brew tap`: {}", String::from_utf8_lossy(&output.stderr)).into()
+                            )
+                        )
+                    )
+                }
+            }))
+    }
+
+    fn configured(&self, handle: &Handle, name: &str, source: &str, key: Option<&str>) -> ExecutableResult {
+        if key.is_some() {
+            return Box::new(future::err("Homebrew taps do not support signing keys".into()));
+        }
+
+        let handle = handle.clone();
+        let source = normalise_remote(source);
+
+        Box::new(process::Command::new("brew")
+            .args(["tap-info", "--json", name])
+            .output_async(&handle)
+            .chain_err(|| "Could not get tap info")
+            .and_then(move |output| {
+                if output.status.success() {
+                    let info: Value = match serde_json::from_slice(&output.stdout) {
+                        Ok(v) => v,
+                        Err(e) => return future::err(Error::with_chain(e, "Could not parse `brew tap-info` output")),
+                    };
+                    // Output is an array with one object per tap, e.g.
+                    // [{"installed": true, "remote": "https://...", ...}]
+                    let tap = &info[0];
+                    let configured = tap["installed"].as_bool().unwrap_or(false) &&
+                        tap["remote"].as_str().map(normalise_remote) == Some(source);
+                    future::ok(
+                        Message::WithoutBody(
+                            ResponseResult::Ok(
+                                Response::Bool(configured))))
+                } else {
+                    future::ok(
+                        Message::WithoutBody(
+                            ResponseResult::Err(
+                                format!("Error running `brew tap-info`: {}", String::from_utf8_lossy(&output.stderr)).into()
+                            )
+                        )
+                    )
+                }
+            }))
+    }
+
+    fn add(&self, handle: &Handle, name: &str, source: &str, key: Option<&str>) -> ExecutableResult {
+        if key.is_some() {
+            return Box::new(future::err("Homebrew taps do not support signing keys".into()));
+        }
+
+        let cmd = match factory() {
+            Ok(c) => c,
+            Err(e) => return Box::new(future::ok(
+                Message::WithoutBody(
+                    ResponseResult::Err(
+                        e.into())))),
+        };
+        // `--custom-remote` lets us change the remote of an existing tap
+        cmd.exec(handle, &["brew", "tap", "--custom-remote", name, source])
+    }
+
+    fn remove(&self, handle: &Handle, name: &str) -> ExecutableResult {
+        let cmd = match factory() {
+            Ok(c) => c,
+            Err(e) => return Box::new(future::ok(
+                Message::WithoutBody(
+                    ResponseResult::Err(
+                        e.into())))),
+        };
+        cmd.exec(handle, &["brew", "untap", name])
+    }
+}
+
+// Git remotes may or may not have a trailing slash or `.git`, but they
+// point to the same place either way.
+fn normalise_remote(remote: &str) -> String {
+    let remote = remote.trim().trim_right_matches('/');
+    remote.trim_right_matches(".git").to_owned()
+}
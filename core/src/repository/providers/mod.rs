@@ -0,0 +1,206 @@
+// Copyright 2015-2017 Intecture Developers.
+//
+// Licensed under the Mozilla Public License 2.0 <LICENSE or
+// https://www.tldrlegal.com/l/mpl-2.0>. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+//! OS abstractions for `Repository`.
+
+mod apt;
+mod dnf;
+mod homebrew;
+mod pkg;
+mod yum;
+
+use command::factory as command_factory;
+use errors::*;
+use futures::future;
+use remote::{ExecutableResult, ResponseResult};
+use std::fs;
+use std::path::{Path, PathBuf};
+pub use self::apt::Apt;
+pub use self::dnf::Dnf;
+pub use self::homebrew::Homebrew;
+pub use self::pkg::Pkg;
+pub use self::yum::Yum;
+use tokio_core::reactor::Handle;
+use tokio_proto::streaming::Message;
+
+/// Specific implementation of `Repository`
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum Provider {
+    Apt,
+    Dnf,
+    Homebrew,
+    Pkg,
+    Yum,
+}
+
+pub trait RepositoryProvider {
+    fn available() -> Result<bool> where Self: Sized;
+    fn exists(&self, &Handle, &str) -> ExecutableResult;
+    fn configured(&self, &Handle, &str, &str, Option<&str>) -> ExecutableResult;
+    fn add(&self, &Handle, &str, &str, Option<&str>) -> ExecutableResult;
+    fn remove(&self, &Handle, &str) -> ExecutableResult;
+}
+
+#[doc(hidden)]
+pub fn factory() -> Result<Box<RepositoryProvider>> {
+    if Apt::available()? {
+        Ok(Box::new(Apt))
+    }
+    else if Dnf::available()? {
+        Ok(Box::new(Dnf))
+    }
+    else if Homebrew::available()? {
+        Ok(Box::new(Homebrew))
+    }
+    else if Pkg::available()? {
+        Ok(Box::new(Pkg))
+    }
+    else if Yum::available()? {
+        Ok(Box::new(Yum))
+    } else {
+        Err(ErrorKind::ProviderUnavailable("Repository".into()).into())
+    }
+}
+
+// Writes a config file, first downloading the signing key if there is one.
+// Positional parameters are: config path, config contents, key URL and key
+// path. The key URL and path are empty if there's no key.
+const WRITE_CONFIG: &'static str = r#"set -e
+if [ -n "$3" ]; then
+    mkdir -p "$(dirname "$4")"
+    if command -v curl >/dev/null 2>&1; then
+        curl -fsSL -o "$4" "$3"
+    elif command -v fetch >/dev/null 2>&1; then
+        fetch -q -o "$4" "$3"
+    else
+        wget -q -O "$4" "$3"
+    fi
+fi
+mkdir -p "$(dirname "$1")"
+printf '%s' "$2" > "$1""#;
+
+// The path to a repository's config file. Names become file names, so they
+// mustn't be able to point anywhere else.
+fn config_path(dir: &str, name: &str, ext: &str) -> Result<PathBuf> {
+    if name.is_empty() || name.starts_with('.') || name.contains('/') {
+        return Err(format!("Invalid repository name: {}", name).into());
+    }
+    check_value("name", name)?;
+
+    Ok(Path::new(dir).join(format!("{}.{}", name, ext)))
+}
+
+// Names, sources and keys end up in config files, where a newline or quote
+// could smuggle in settings of their own, e.g. `gpgcheck=0`.
+fn check_value(what: &str, value: &str) -> Result<()> {
+    if value.contains(|c: char| c.is_control() || c == '"' || c == '\'') {
+        return Err(format!("Invalid repository {}: {:?}", what, value).into());
+    }
+    Ok(())
+}
+
+// A comment recording where a repository's signing key came from. Config
+// files that fetch a key start with this, so that if the key's URL changes,
+// the config no longer matches and the key is fetched again.
+fn key_source(url: &str) -> Result<String> {
+    check_value("key", url)?;
+    Ok(format!("# Signing key: {}\n", url))
+}
+
+// Whether the config file at `path` contains exactly `contents`, and the
+// signing key, if any, has been downloaded.
+fn is_configured(path: &Path, contents: &str, key: Option<&Path>) -> bool {
+    let current = match fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(_) => return false,
+    };
+
+    current == contents && key.map(|k| k.exists()).unwrap_or(true)
+}
+
+fn write_config(handle: &Handle, path: &Path, contents: &str, key: Option<(&str, &Path)>) -> ExecutableResult {
+    let cmd = match command_factory() {
+        Ok(c) => c,
+        Err(e) => return Box::new(future::ok(
+            Message::WithoutBody(
+                ResponseResult::Err(
+                    e.into())))),
+    };
+
+    let (key_url, key_path) = match key {
+        Some((url, path)) => (url, path.to_string_lossy().into_owned()),
+        None => ("", String::new()),
+    };
+    cmd.exec(handle, &["/bin/sh", "-c", WRITE_CONFIG, "sh", &path.to_string_lossy(), contents, key_url, &key_path])
+}
+
+fn remove_config(handle: &Handle, paths: &[PathBuf]) -> ExecutableResult {
+    let cmd = match command_factory() {
+        Ok(c) => c,
+        Err(e) => return Box::new(future::ok(
+            Message::WithoutBody(
+                ResponseResult::Err(
+                    e.into())))),
+    };
+
+    let paths: Vec<String> = paths.iter().map(|p| p.to_string_lossy().into_owned()).collect();
+    let mut args = vec!["rm", "-f"];
+    args.extend(paths.iter().map(|p| &**p));
+    cmd.exec(handle, &args)
+}
+
+// Yum and Dnf share the same .repo file format.
+fn rpm_repo(name: &str, baseurl: &str, key: Option<&str>) -> Result<String> {
+    check_value("name", name)?;
+    check_value("source", baseurl)?;
+    let mut repo = format!("[{}]\nname={}\nbaseurl={}\nenabled=1\n", name, name, baseurl);
+    match key {
+        Some(key) => {
+            check_value("key", key)?;
+            repo.push_str(&format!("gpgcheck=1\ngpgkey={}\n", key));
+        },
+        None => repo.push_str("gpgcheck=0\n"),
+    }
+    Ok(repo)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use super::{config_path, key_source, rpm_repo};
+
+    #[test]
+    fn test_config_path() {
+        assert_eq!(config_path("/etc/yum.repos.d", "epel", "repo").unwrap(), PathBuf::from("/etc/yum.repos.d/epel.repo"));
+        assert_eq!(config_path("/etc/yum.repos.d", "epel.testing", "repo").unwrap(), PathBuf::from("/etc/yum.repos.d/epel.testing.repo"));
+        assert!(config_path("/etc/yum.repos.d", "", "repo").is_err());
+        assert!(config_path("/etc/yum.repos.d", ".hidden", "repo").is_err());
+        assert!(config_path("/etc/yum.repos.d", "..", "repo").is_err());
+        assert!(config_path("/etc/yum.repos.d", "../../etc/passwd", "repo").is_err());
+        assert!(config_path("/etc/yum.repos.d", "/etc/passwd", "repo").is_err());
+        assert!(config_path("/etc/yum.repos.d", "epel\ngpgcheck=0", "repo").is_err());
+        assert!(config_path("/etc/yum.repos.d", "epel\"", "repo").is_err());
+    }
+
+    #[test]
+    fn test_key_source() {
+        assert_eq!(key_source("https://example.com/key").unwrap(), "# Signing key: https://example.com/key\n");
+        assert!(key_source("https://example.com/key\ndeb https://example.com/evil focal main").is_err());
+    }
+
+    #[test]
+    fn test_rpm_repo() {
+        assert_eq!(rpm_repo("epel", "https://example.com/epel", None).unwrap(),
+                   "[epel]\nname=epel\nbaseurl=https://example.com/epel\nenabled=1\ngpgcheck=0\n");
+        assert_eq!(rpm_repo("epel", "https://example.com/epel", Some("https://example.com/key")).unwrap(),
+                   "[epel]\nname=epel\nbaseurl=https://example.com/epel\nenabled=1\ngpgcheck=1\ngpgkey=https://example.com/key\n");
+
+        // Values can't add settings of their own
+        assert!(rpm_repo("epel", "https://example.com/epel\ngpgcheck=0", None).is_err());
+        assert!(rpm_repo("epel", "https://example.com/epel", Some("https://example.com/key\r\ngpgcheck=0")).is_err());
+        assert!(rpm_repo("epel]\n[other", "https://example.com/epel", None).is_err());
+    }
+}
@@ -0,0 +1,101 @@
+// Copyright 2015-2017 Intecture Developers.
+//
+// Licensed under the Mozilla Public License 2.0 <LICENSE or
+// https://www.tldrlegal.com/l/mpl-2.0>. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+use errors::*;
+use futures::future;
+use remote::{ExecutableResult, Response, ResponseResult};
+use std::path::PathBuf;
+use std::process;
+use super::{check_value, config_path, is_configured, key_source, remove_config, write_config, RepositoryProvider};
+use tokio_core::reactor::Handle;
+use tokio_proto::streaming::Message;
+
+const REPOS_DIR: &'static str = "/usr/local/etc/pkg/repos";
+const KEYS_DIR: &'static str = "/usr/local/etc/pkg/keys";
+
+pub struct Pkg;
+
+impl RepositoryProvider for Pkg {
+    fn available() -> Result<bool> {
+        Ok(process::Command::new("/usr/bin/type")
+            .arg("pkg")
+            .status()
+            .chain_err(|| "Could not determine provider availability")?
+            .success())
+    }
+
+    fn exists(&self, _: &Handle, name: &str) -> ExecutableResult {
+        let paths = match repo_files(name) {
+            Ok(p) => p,
+            Err(e) => return Box::new(future::err(e)),
+        };
+        Box::new(future::ok(
+            Message::WithoutBody(
+                ResponseResult::Ok(
+                    Response::Bool(paths.iter().any(|p| p.exists()))))))
+    }
+
+    fn configured(&self, _: &Handle, name: &str, source: &str, key: Option<&str>) -> ExecutableResult {
+        let (path, contents, key_path) = match repo_conf(name, source, key) {
+            Ok(c) => c,
+            Err(e) => return Box::new(future::err(e)),
+        };
+        let configured = is_configured(&path, &contents, key_path.as_deref());
+        Box::new(future::ok(
+            Message::WithoutBody(
+                ResponseResult::Ok(
+                    Response::Bool(configured)))))
+    }
+
+    fn add(&self, handle: &Handle, name: &str, source: &str, key: Option<&str>) -> ExecutableResult {
+        let (path, contents, key_path) = match repo_conf(name, source, key) {
+            Ok(c) => c,
+            Err(e) => return Box::new(future::err(e)),
+        };
+        let key = match (key, key_path.as_ref()) {
+            (Some(url), Some(path)) => Some((url, &**path)),
+            _ => None,
+        };
+        write_config(handle, &path, &contents, key)
+    }
+
+    fn remove(&self, handle: &Handle, name: &str) -> ExecutableResult {
+        let paths = match repo_files(name) {
+            Ok(p) => p,
+            Err(e) => return Box::new(future::err(e)),
+        };
+        remove_config(handle, &paths)
+    }
+}
+
+// Every file that a repository may have left behind, including a key that
+// outlived its config file.
+fn repo_files(name: &str) -> Result<Vec<PathBuf>> {
+    Ok(vec![config_path(REPOS_DIR, name, "conf")?, config_path(KEYS_DIR, name, "pub")?])
+}
+
+// The path and contents of a repository's config file, and the path of its
+// public key, if it has one.
+fn repo_conf(name: &str, source: &str, key: Option<&str>) -> Result<(PathBuf, String, Option<PathBuf>)> {
+    let path = config_path(REPOS_DIR, name, "conf")?;
+    let key_path = match key {
+        Some(_) => Some(config_path(KEYS_DIR, name, "pub")?),
+        None => None,
+    };
+
+    check_value("source", source)?;
+    let mut contents = match key {
+        Some(url) => key_source(url)?,
+        None => String::new(),
+    };
+    contents.push_str(&format!("{}: {{\n  url: \"{}\",\n  enabled: yes", name, source));
+    if let Some(ref key) = key_path {
+        contents.push_str(&format!(",\n  signature_type: \"pubkey\",\n  pubkey: \"{}\"", key.display()));
+    }
+    contents.push_str("\n}\n");
+
+    Ok((path, contents, key_path))
+}
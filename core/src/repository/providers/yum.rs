@@ -0,0 +1,75 @@
+// Copyright 2015-2017 Intecture Developers.
+//
+// Licensed under the Mozilla Public License 2.0 <LICENSE or
+// https://www.tldrlegal.com/l/mpl-2.0>. This file may not be copied,
+// modified, or distributed except according to those terms.
+
+use errors::*;
+use futures::future;
+use remote::{ExecutableResult, Response, ResponseResult};
+use std::process;
+use super::{config_path, is_configured, remove_config, rpm_repo, write_config, RepositoryProvider};
+use tokio_core::reactor::Handle;
+use tokio_proto::streaming::Message;
+
+const REPOS_DIR: &'static str = "/etc/yum.repos.d";
+
+pub struct Yum;
+
+impl RepositoryProvider for Yum {
+    fn available() -> Result<bool> {
+        Ok(process::Command::new("/usr/bin/type")
+            .arg("yum")
+            .status()
+            .chain_err(|| "Could not determine provider availability")?
+            .success())
+    }
+
+    fn exists(&self, _: &Handle, name: &str) -> ExecutableResult {
+        let path = match config_path(REPOS_DIR, name, "repo") {
+            Ok(p) => p,
+            Err(e) => return Box::new(future::err(e)),
+        };
+        Box::new(future::ok(
+            Message::WithoutBody(
+                ResponseResult::Ok(
+                    Response::Bool(path.exists())))))
+    }
+
+    fn configured(&self, _: &Handle, name: &str, source: &str, key: Option<&str>) -> ExecutableResult {
+        let path = match config_path(REPOS_DIR, name, "repo") {
+            Ok(p) => p,
+            Err(e) => return Box::new(future::err(e)),
+        };
+        let contents = match rpm_repo(name, source, key) {
+            Ok(c) => c,
+            Err(e) => return Box::new(future::err(e)),
+        };
+        // Yum imports the key itself the first time it's needed
+        let configured = is_configured(&path, &contents, None);
+        Box::new(future::ok(
+            Message::WithoutBody(
+                ResponseResult::Ok(
+                    Response::Bool(configured)))))
+    }
+
+    fn add(&self, handle: &Handle, name: &str, source: &str, key: Option<&str>) -> ExecutableResult {
+        let path = match config_path(REPOS_DIR, name, "repo") {
+            Ok(p) => p,
+            Err(e) => return Box::new(future::err(e)),
+        };
+        let contents = match rpm_repo(name, source, key) {
+            Ok(c) => c,
+            Err(e) => return Box::new(future::err(e)),
+        };
+        write_config(handle, &path, &contents, None)
+    }
+
+    fn remove(&self, handle: &Handle, name: &str) -> ExecutableResult {
+        let path = match config_path(REPOS_DIR, name, "repo") {
+            Ok(p) => p,
+            Err(e) => return Box::new(future::err(e)),
+        };
+        remove_config(handle, &[path])
+    }
+}
@@ -109,7 +109,7 @@ impl<H: Host + 'static> Service<H> {
     pub fn running(&self) -> Box<Future<Item = bool, Error = Error>> {
         let request = Request::ServiceRunning(self.provider, self.name.clone());
         Box::new(self.host.request(request)
-            .chain_err(|| ErrorKind::Request { endpoint: "Service", func: "running" })
+            .chain_err(|| ErrorKind::Request { endpoint: "Service".into(), func: "running".into() })
             .map(|msg| {
                 match msg.into_inner() {
                     Response::Bool(b) => b,
@@ -166,16 +166,17 @@ impl<H: Host + 'static> Service<H> {
         -> Box<Future<Item = Option<CommandStatus>, Error = Error>>
     {
         let request = Request::ServiceAction(provider, name.into(), action.into());
+        let host = host.clone();
         Box::new(host.request(request)
-            .chain_err(|| ErrorKind::Request { endpoint: "Service", func: "action" })
-            .map(|msg| Some(CommandStatus::new(msg))))
+            .chain_err(|| ErrorKind::Request { endpoint: "Service".into(), func: "action".into() })
+            .and_then(move |msg| CommandStatus::new(&host, msg).map(Some)))
     }
 
     /// Check if the service will start at boot.
     pub fn enabled(&self) -> Box<Future<Item = bool, Error = Error>> {
         let request = Request::ServiceEnabled(self.provider, self.name.clone());
         Box::new(self.host.request(request)
-            .chain_err(|| ErrorKind::Request { endpoint: "Service", func: "enabled" })
+            .chain_err(|| ErrorKind::Request { endpoint: "Service".into(), func: "enabled".into() })
             .map(|msg| {
                 match msg.into_inner() {
                     Response::Bool(b) => b,
@@ -211,7 +212,7 @@ impl<H: Host + 'static> Service<H> {
                 } else {
                     let request = Request::ServiceEnable(provider, name);
                     Box::new(host.request(request)
-                        .chain_err(|| ErrorKind::Request { endpoint: "Service", func: "enable" })
+                        .chain_err(|| ErrorKind::Request { endpoint: "Service".into(), func: "enable".into() })
                         .map(|msg| match msg.into_inner() {
                             Response::Null => Some(()),
                             _ => unreachable!(),
@@ -245,7 +246,7 @@ impl<H: Host + 'static> Service<H> {
                 if enabled {
                     let request = Request::ServiceDisable(provider, name);
                     Box::new(host.request(request)
-                        .chain_err(|| ErrorKind::Request { endpoint: "Service", func: "disable" })
+                        .chain_err(|| ErrorKind::Request { endpoint: "Service".into(), func: "disable".into() })
                         .map(|msg| match msg.into_inner() {
                             Response::Null => Some(()),
                             _ => unreachable!(),
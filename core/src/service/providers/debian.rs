@@ -5,7 +5,6 @@
 // modified, or distributed except according to those terms.
 
 use command::factory;
-use error_chain::ChainedError;
 use errors::*;
 use futures::{future, Future};
 use regex::Regex;
@@ -37,7 +36,7 @@ impl ServiceProvider for Debian {
         Box::new(status.map(|s| Message::WithoutBody(
                 ResponseResult::Ok(
                     Response::Bool(s.success()))))
-            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("service <service> status"))))
+            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("service <service> status".into()))))
     }
 
     fn action(&self, handle: &Handle, name: &str, action: &str) -> ExecutableResult {
@@ -46,7 +45,7 @@ impl ServiceProvider for Debian {
             Err(e) => return Box::new(future::ok(
                 Message::WithoutBody(
                     ResponseResult::Err(
-                        format!("{}", e.display_chain()))))),
+                        e.into())))),
         };
         cmd.exec(handle, &["service", action, name])
     }
@@ -56,7 +55,7 @@ impl ServiceProvider for Debian {
 
         Box::new(process::Command::new("/sbin/runlevel")
             .output_async(&handle)
-            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("/sbin/runlevel")))
+            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("/sbin/runlevel".into())))
             .and_then(move |output| {
                 if output.status.success() {
                     let mut stdout = (*String::from_utf8_lossy(&output.stdout)).to_owned();
@@ -86,7 +85,7 @@ impl ServiceProvider for Debian {
 
                     future::ok(Message::WithoutBody(ResponseResult::Ok(Response::Bool(enabled))))
                 } else {
-                    future::err(ErrorKind::SystemCommand("/usr/bin/runlevel").into())
+                    future::err(ErrorKind::SystemCommand("/usr/bin/runlevel".into()).into())
                 }
             }))
     }
@@ -100,10 +99,10 @@ impl ServiceProvider for Debian {
                     Message::WithoutBody(ResponseResult::Ok(Response::Null))
                 } else {
                     Message::WithoutBody(ResponseResult::Err(
-                        format!("Could not enable service: {}", String::from_utf8_lossy(&out.stderr))))
+                        format!("Could not enable service: {}", String::from_utf8_lossy(&out.stderr)).into()))
                 }
             })
-            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("update-rc.d enable <service>"))))
+            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("update-rc.d enable <service>".into()))))
     }
 
     fn disable(&self, handle: &Handle, name: &str) -> ExecutableResult {
@@ -115,9 +114,9 @@ impl ServiceProvider for Debian {
                     Message::WithoutBody(ResponseResult::Ok(Response::Null))
                 } else {
                     Message::WithoutBody(ResponseResult::Err(
-                        format!("Could not disable service: {}", String::from_utf8_lossy(&out.stderr))))
+                        format!("Could not disable service: {}", String::from_utf8_lossy(&out.stderr)).into()))
                 }
             })
-            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("update-rc.d disable <service>"))))
+            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("update-rc.d disable <service>".into()))))
     }
 }
@@ -4,7 +4,6 @@
 // https://www.tldrlegal.com/l/mpl-2.0>. This file may not be copied,
 // modified, or distributed except according to those terms.
 
-use error_chain::ChainedError;
 use errors::*;
 use futures::future;
 use remote::{ExecutableResult, ResponseResult};
@@ -56,7 +55,7 @@ impl ServiceProvider for Homebrew {
             return Box::new(future::ok(
                 Message::WithoutBody(
                     ResponseResult::Err(
-                        format!("{}", e.display_chain())))))
+                        e.into()))))
         }
 
         self.inner.action(handle, name, action)
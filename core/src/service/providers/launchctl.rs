@@ -5,7 +5,6 @@
 // modified, or distributed except according to those terms.
 
 use command::factory;
-use error_chain::ChainedError;
 use errors::*;
 use futures::{future, Future};
 use regex::Regex;
@@ -91,7 +90,7 @@ impl ServiceProvider for Launchctl {
         Box::new(status.map(|s| Message::WithoutBody(
                 ResponseResult::Ok(
                     Response::Bool(s.success()))))
-            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("launchctl blame"))))
+            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("launchctl blame".into()))))
     }
 
     fn action(&self, handle: &Handle, name: &str, action: &str) -> ExecutableResult {
@@ -107,7 +106,7 @@ impl ServiceProvider for Launchctl {
             Err(e) => return Box::new(future::ok(
                 Message::WithoutBody(
                     ResponseResult::Err(
-                        format!("{}", e.display_chain()))))),
+                        e.into())))),
         };
 
         // Run through shell as `action` may contain multiple args with spaces.
@@ -126,7 +125,7 @@ impl ServiceProvider for Launchctl {
         Box::new(process::Command::new("/bin/launchctl")
             .args(&["print-disabled", &self.domain_target])
             .output_async(handle)
-            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("launchctl print-disabled <domain_target>")))
+            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("launchctl print-disabled <domain_target>".into())))
             .and_then(move |out| {
                 if out.status.success() {
                     let re = match Regex::new(&format!("^\\s+\"{}\" => false", name)) {
@@ -138,7 +137,7 @@ impl ServiceProvider for Launchctl {
 
                     future::ok(Message::WithoutBody(ResponseResult::Ok(Response::Bool(is_match))))
                 } else {
-                    future::err(ErrorKind::SystemCommand("/bin/launchctl").into())
+                    future::err(ErrorKind::SystemCommand("/bin/launchctl".into()).into())
                 }
             }))
     }
@@ -152,10 +151,10 @@ impl ServiceProvider for Launchctl {
                     Message::WithoutBody(ResponseResult::Ok(Response::Null))
                 } else {
                     Message::WithoutBody(ResponseResult::Err(
-                        format!("Could not enable service: {}", String::from_utf8_lossy(&out.stderr))))
+                        format!("Could not enable service: {}", String::from_utf8_lossy(&out.stderr)).into()))
                 }
             })
-            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("launchctl enable <service>"))))
+            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("launchctl enable <service>".into()))))
     }
 
     fn disable(&self, handle: &Handle, name: &str) -> ExecutableResult {
@@ -167,9 +166,9 @@ impl ServiceProvider for Launchctl {
                     Message::WithoutBody(ResponseResult::Ok(Response::Null))
                 } else {
                     Message::WithoutBody(ResponseResult::Err(
-                        format!("Could not disable service: {}", String::from_utf8_lossy(&out.stderr))))
+                        format!("Could not disable service: {}", String::from_utf8_lossy(&out.stderr)).into()))
                 }
             })
-            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("launchctl disable <service>"))))
+            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("launchctl disable <service>".into()))))
     }
 }
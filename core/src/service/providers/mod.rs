@@ -59,6 +59,6 @@ pub fn factory(telemetry: &Telemetry) -> Result<Box<ServiceProvider>> {
     } else if Redhat::available(telemetry)? {
         Ok(Box::new(Redhat))
     } else {
-        Err(ErrorKind::ProviderUnavailable("Service").into())
+        Err(ErrorKind::ProviderUnavailable("Service".into()).into())
     }
 }
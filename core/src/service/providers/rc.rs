@@ -5,7 +5,6 @@
 // modified, or distributed except according to those terms.
 
 use command::factory;
-use error_chain::ChainedError;
 use errors::*;
 use futures::{future, Future};
 use regex::Regex;
@@ -36,7 +35,7 @@ impl ServiceProvider for Rc {
         Box::new(status.map(|s| Message::WithoutBody(
                 ResponseResult::Ok(
                     Response::Bool(s.success()))))
-            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("service <service> status"))))
+            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("service <service> status".into()))))
     }
 
     fn action(&self, handle: &Handle, name: &str, action: &str) -> ExecutableResult {
@@ -45,7 +44,7 @@ impl ServiceProvider for Rc {
             Err(e) => return Box::new(future::ok(
                 Message::WithoutBody(
                     ResponseResult::Err(
-                        format!("{}", e.display_chain()))))),
+                        e.into())))),
         };
         cmd.exec(handle, &["service", action, name])
     }
@@ -56,7 +55,7 @@ impl ServiceProvider for Rc {
         Box::new(process::Command::new("/usr/sbin/sysrc")
             .arg(&format!("{}_enable", name)) // XXX Assuming "_enable" is the correct suffix
             .output_async(&handle)
-            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("/usr/sbin/sysrc <service>_enable")))
+            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("/usr/sbin/sysrc <service>_enable".into())))
             .and_then(move |output| {
                 if output.status.success() {
                     let re = match Regex::new(&format!("^{}_enable: (?i:no)", name)) {
@@ -84,10 +83,10 @@ impl ServiceProvider for Rc {
                     Message::WithoutBody(ResponseResult::Ok(Response::Null))
                 } else {
                     Message::WithoutBody(ResponseResult::Err(
-                        format!("Could not enable service: {}", String::from_utf8_lossy(&out.stderr))))
+                        format!("Could not enable service: {}", String::from_utf8_lossy(&out.stderr)).into()))
                 }
             })
-            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("systemctl enable <service>"))))
+            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("systemctl enable <service>".into()))))
     }
 
     fn disable(&self, handle: &Handle, name: &str) -> ExecutableResult {
@@ -99,9 +98,9 @@ impl ServiceProvider for Rc {
                     Message::WithoutBody(ResponseResult::Ok(Response::Null))
                 } else {
                     Message::WithoutBody(ResponseResult::Err(
-                        format!("Could not disable service: {}", String::from_utf8_lossy(&out.stderr))))
+                        format!("Could not disable service: {}", String::from_utf8_lossy(&out.stderr)).into()))
                 }
             })
-            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("systemctl disable <service>"))))
+            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("systemctl disable <service>".into()))))
     }
 }
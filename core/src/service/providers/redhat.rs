@@ -5,7 +5,6 @@
 // modified, or distributed except according to those terms.
 
 use command::factory;
-use error_chain::ChainedError;
 use errors::*;
 use futures::{future, Future};
 use remote::{ExecutableResult, Response, ResponseResult};
@@ -35,7 +34,7 @@ impl ServiceProvider for Redhat {
         Box::new(status.map(|s| Message::WithoutBody(
                 ResponseResult::Ok(
                     Response::Bool(s.success()))))
-            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("service <service> status"))))
+            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("service <service> status".into()))))
     }
 
     fn action(&self, handle: &Handle, name: &str, action: &str) -> ExecutableResult {
@@ -44,7 +43,7 @@ impl ServiceProvider for Redhat {
             Err(e) => return Box::new(future::ok(
                 Message::WithoutBody(
                     ResponseResult::Err(
-                        format!("{}", e.display_chain()))))),
+                        e.into())))),
         };
         cmd.exec(handle, &["service", action, name])
     }
@@ -61,7 +60,7 @@ impl ServiceProvider for Redhat {
         Box::new(status.map(|s| Message::WithoutBody(
                 ResponseResult::Ok(
                     Response::Bool(s.success()))))
-            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("chkconfig <service>"))))
+            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("chkconfig <service>".into()))))
     }
 
     fn enable(&self, handle: &Handle, name: &str) -> ExecutableResult {
@@ -73,10 +72,10 @@ impl ServiceProvider for Redhat {
                     Message::WithoutBody(ResponseResult::Ok(Response::Null))
                 } else {
                     Message::WithoutBody(ResponseResult::Err(
-                        format!("Could not enable service: {}", String::from_utf8_lossy(&out.stderr))))
+                        format!("Could not enable service: {}", String::from_utf8_lossy(&out.stderr)).into()))
                 }
             })
-            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("chkconfig <service> on"))))
+            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("chkconfig <service> on".into()))))
     }
 
     fn disable(&self, handle: &Handle, name: &str) -> ExecutableResult {
@@ -88,9 +87,9 @@ impl ServiceProvider for Redhat {
                     Message::WithoutBody(ResponseResult::Ok(Response::Null))
                 } else {
                     Message::WithoutBody(ResponseResult::Err(
-                        format!("Could not disable service: {}", String::from_utf8_lossy(&out.stderr))))
+                        format!("Could not disable service: {}", String::from_utf8_lossy(&out.stderr)).into()))
                 }
             })
-            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("chkconfig <service> off"))))
+            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("chkconfig <service> off".into()))))
     }
 }
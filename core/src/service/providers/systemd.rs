@@ -5,7 +5,6 @@
 // modified, or distributed except according to those terms.
 
 use command::factory;
-use error_chain::ChainedError;
 use errors::*;
 use futures::{future, Future};
 use remote::{ExecutableResult, Response, ResponseResult};
@@ -29,7 +28,7 @@ impl ServiceProvider for Systemd {
             let out = String::from_utf8_lossy(&output.stdout);
             Ok(out.contains("systemd"))
         } else {
-            Err(ErrorKind::SystemCommand("/usr/bin/stat").into())
+            Err(ErrorKind::SystemCommand("/usr/bin/stat".into()).into())
         }
     }
 
@@ -45,7 +44,7 @@ impl ServiceProvider for Systemd {
         Box::new(status.map(|s| Message::WithoutBody(
                 ResponseResult::Ok(
                     Response::Bool(s.success()))))
-            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("systemctl is-active"))))
+            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("systemctl is-active".into()))))
     }
 
     fn action(&self, handle: &Handle, name: &str, action: &str) -> ExecutableResult {
@@ -54,7 +53,7 @@ impl ServiceProvider for Systemd {
             Err(e) => return Box::new(future::ok(
                 Message::WithoutBody(
                     ResponseResult::Err(
-                        format!("{}", e.display_chain()))))),
+                        e.into())))),
         };
         cmd.exec(handle, &["systemctl", action, name])
     }
@@ -71,7 +70,7 @@ impl ServiceProvider for Systemd {
         Box::new(status.map(|s| Message::WithoutBody(
                 ResponseResult::Ok(
                     Response::Bool(s.success()))))
-            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("systemctl is-enabled"))))
+            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("systemctl is-enabled".into()))))
     }
 
     fn enable(&self, handle: &Handle, name: &str) -> ExecutableResult {
@@ -83,10 +82,10 @@ impl ServiceProvider for Systemd {
                     Message::WithoutBody(ResponseResult::Ok(Response::Null))
                 } else {
                     Message::WithoutBody(ResponseResult::Err(
-                        format!("Could not enable service: {}", String::from_utf8_lossy(&out.stderr))))
+                        format!("Could not enable service: {}", String::from_utf8_lossy(&out.stderr)).into()))
                 }
             })
-            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("systemctl enable <service>"))))
+            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("systemctl enable <service>".into()))))
     }
 
     fn disable(&self, handle: &Handle, name: &str) -> ExecutableResult {
@@ -98,9 +97,9 @@ impl ServiceProvider for Systemd {
                     Message::WithoutBody(ResponseResult::Ok(Response::Null))
                 } else {
                     Message::WithoutBody(ResponseResult::Err(
-                        format!("Could not disable service: {}", String::from_utf8_lossy(&out.stderr))))
+                        format!("Could not disable service: {}", String::from_utf8_lossy(&out.stderr)).into()))
                 }
             })
-            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("systemctl disable <service>"))))
+            .map_err(|e| Error::with_chain(e, ErrorKind::SystemCommand("systemctl disable <service>".into()))))
     }
 }
@@ -123,14 +123,20 @@ pub struct User {
     pub group: String,
     pub gid: u32,
     pub home_dir: PathBuf,
+    /// The user whose privileges we have, which differs from `user` when
+    /// running setuid.
+    pub effective_user: String,
+    pub effective_uid: u32,
+    pub effective_group: String,
+    pub effective_gid: u32,
 }
 
 impl Telemetry {
     pub fn load<H: Host>(host: &H) -> Box<Future<Item = Telemetry, Error = Error>> {
         Box::new(host.request(Request::TelemetryLoad)
-            .chain_err(|| ErrorKind::Request { endpoint: "Telemetry", func: "load" })
+            .chain_err(|| ErrorKind::Request { endpoint: "Telemetry".into(), func: "load".into() })
             .map(|msg| match msg.into_inner() {
-                Response::TelemetryLoad(t) => Telemetry::from(t),
+                Response::TelemetryLoad(t) => Telemetry::from(*t),
                 _ => unreachable!(),
             }))
     }
@@ -141,4 +147,10 @@ impl User {
     pub fn is_root(&self) -> bool {
         self.uid == 0
     }
+
+    // Whether we have root privileges, which is calculated as
+    // `effective_uid == 0`.
+    pub fn is_effective_root(&self) -> bool {
+        self.effective_uid == 0
+    }
 }
@@ -31,7 +31,7 @@ impl TelemetryProvider for Debian {
 
             future::ok(Message::WithoutBody(
                 ResponseResult::Ok(
-                    Response::TelemetryLoad(t.into()))))
+                    Response::TelemetryLoad(Box::new(t.into())))))
         }))
     }
 }
@@ -66,17 +66,17 @@ fn version() -> Result<(String, u32, u32)> {
     let out = process::Command::new("lsb_release")
                                .arg("-sr")
                                .output()
-                               .chain_err(|| ErrorKind::SystemCommand("lsb_release"))?;
+                               .chain_err(|| ErrorKind::SystemCommand("lsb_release".into()))?;
     let version_str = str::from_utf8(&out.stdout)
-                          .chain_err(|| ErrorKind::SystemCommandOutput("lsb_release"))?
+                          .chain_err(|| ErrorKind::SystemCommandOutput("lsb_release".into()))?
                           .trim()
                           .to_owned();
     let (maj, min) = {
         let mut parts = version_str.split('.');
         let errstr = format!("Expected OS version format `u32.u32`, got: '{}'", version_str);
         (
-            parts.next().ok_or(&*errstr)?.parse().chain_err(|| ErrorKind::SystemCommandOutput("sw_vers"))?,
-            parts.next().ok_or(&*errstr)?.parse().chain_err(|| ErrorKind::SystemCommandOutput("sw_vers"))?
+            parts.next().ok_or(&*errstr)?.parse().chain_err(|| ErrorKind::SystemCommandOutput("sw_vers".into()))?,
+            parts.next().ok_or(&*errstr)?.parse().chain_err(|| ErrorKind::SystemCommandOutput("sw_vers".into()))?
         )
     };
     Ok((version_str, maj, min))
@@ -31,7 +31,7 @@ impl TelemetryProvider for Fedora {
 
             future::ok(Message::WithoutBody(
                 ResponseResult::Ok(
-                    Response::TelemetryLoad(t.into()))))
+                    Response::TelemetryLoad(Box::new(t.into())))))
         }))
     }
 }
@@ -32,7 +32,7 @@ impl TelemetryProvider for Freebsd {
 
             future::ok(Message::WithoutBody(
                 ResponseResult::Ok(
-                    Response::TelemetryLoad(t.into()))))
+                    Response::TelemetryLoad(Box::new(t.into())))))
         }))
     }
 }
@@ -71,14 +71,14 @@ fn do_load() -> Result<Telemetry> {
 
 fn telemetry_cpu_vendor() -> Result<String> {
     let mut fh = fs::File::open("/var/run/dmesg.boot")
-                          .chain_err(|| ErrorKind::SystemFile("/var/run/dmesg.boot"))?;
+                          .chain_err(|| ErrorKind::SystemFile("/var/run/dmesg.boot".into()))?;
     let mut fc = String::new();
-    fh.read_to_string(&mut fc).chain_err(|| ErrorKind::SystemFileOutput("/var/run/dmesg.boot"))?;
+    fh.read_to_string(&mut fc).chain_err(|| ErrorKind::SystemFileOutput("/var/run/dmesg.boot".into()))?;
 
     let regex = Regex::new(r#"(?m)^CPU:.+$\n\s+Origin="([A-Za-z]+)""#).unwrap();
     if let Some(cap) = regex.captures(&fc) {
         Ok(cap.get(1).unwrap().as_str().into())
     } else {
-        Err(ErrorKind::SystemFileOutput("/var/run/dmesg.boot").into())
+        Err(ErrorKind::SystemFileOutput("/var/run/dmesg.boot".into()).into())
     }
 }
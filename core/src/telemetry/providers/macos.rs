@@ -30,7 +30,7 @@ impl TelemetryProvider for Macos {
 
             future::ok(Message::WithoutBody(
                 ResponseResult::Ok(
-                    Response::TelemetryLoad(t.into()))))
+                    Response::TelemetryLoad(Box::new(t.into())))))
         }))
     }
 }
@@ -81,18 +81,18 @@ fn version() -> Result<(String, u32, u32, u32)> {
     let out = process::Command::new("sw_vers")
                                .arg("-productVersion")
                                .output()
-                               .chain_err(|| ErrorKind::SystemCommand("sw_vers"))?;
+                               .chain_err(|| ErrorKind::SystemCommand("sw_vers".into()))?;
     let version_str = str::from_utf8(&out.stdout)
-                          .chain_err(|| ErrorKind::SystemCommandOutput("sw_vers"))?
+                          .chain_err(|| ErrorKind::SystemCommandOutput("sw_vers".into()))?
                           .trim()
                           .to_owned();
     let (maj, min, patch) = {
         let mut parts = version_str.split('.');
         let errstr = format!("Expected OS version format `u32.u32[.u32]`, got: '{}'", version_str);
         (
-            parts.next().ok_or(&*errstr)?.parse().chain_err(|| ErrorKind::SystemCommandOutput("sw_vers"))?,
-            parts.next().ok_or(&*errstr)?.parse().chain_err(|| ErrorKind::SystemCommandOutput("sw_vers"))?,
-            parts.next().unwrap_or("0").parse().chain_err(|| ErrorKind::SystemCommandOutput("sw_vers"))?
+            parts.next().ok_or(&*errstr)?.parse().chain_err(|| ErrorKind::SystemCommandOutput("sw_vers".into()))?,
+            parts.next().ok_or(&*errstr)?.parse().chain_err(|| ErrorKind::SystemCommandOutput("sw_vers".into()))?,
+            parts.next().unwrap_or("0").parse().chain_err(|| ErrorKind::SystemCommandOutput("sw_vers".into()))?
         )
     };
     Ok((version_str, maj, min, patch))
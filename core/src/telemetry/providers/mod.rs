@@ -53,6 +53,6 @@ pub fn factory() -> Result<Box<TelemetryProvider>> {
     else if Ubuntu::available() {
         Ok(Box::new(Ubuntu))
     } else {
-        Err(ErrorKind::ProviderUnavailable("Telemetry").into())
+        Err(ErrorKind::ProviderUnavailable("Telemetry".into()).into())
     }
 }
@@ -31,7 +31,7 @@ impl TelemetryProvider for Nixos {
 
             future::ok(Message::WithoutBody(
                 ResponseResult::Ok(
-                    Response::TelemetryLoad(t.into()))))
+                    Response::TelemetryLoad(Box::new(t.into())))))
         }))
     }
 }
@@ -65,18 +65,18 @@ fn do_load() -> Result<Telemetry> {
 fn version() -> Result<(String, u32, u32, u32)> {
     let out = process::Command::new("nixos-version")
                                .output()
-                               .chain_err(|| ErrorKind::SystemCommand("nixos-version"))?;
+                               .chain_err(|| ErrorKind::SystemCommand("nixos-version".into()))?;
     let version_str = str::from_utf8(&out.stdout)
-                          .chain_err(|| ErrorKind::SystemCommandOutput("nixos-version"))?
+                          .chain_err(|| ErrorKind::SystemCommandOutput("nixos-version".into()))?
                           .trim()
                           .to_owned();
     let (maj, min, patch) = {
         let mut parts = version_str.split('.');
         let errstr = format!("Expected OS version format `u32.u32.u32.hash (codename)`, got: '{}'", version_str);
         (
-            parts.next().ok_or(&*errstr)?.parse().chain_err(|| ErrorKind::SystemCommandOutput("nixos-version"))?,
-            parts.next().ok_or(&*errstr)?.parse().chain_err(|| ErrorKind::SystemCommandOutput("nixos-version"))?,
-            parts.next().unwrap_or("0").parse().chain_err(|| ErrorKind::SystemCommandOutput("nixos-version"))?
+            parts.next().ok_or(&*errstr)?.parse().chain_err(|| ErrorKind::SystemCommandOutput("nixos-version".into()))?,
+            parts.next().ok_or(&*errstr)?.parse().chain_err(|| ErrorKind::SystemCommandOutput("nixos-version".into()))?,
+            parts.next().unwrap_or("0").parse().chain_err(|| ErrorKind::SystemCommandOutput("nixos-version".into()))?
         )
     };
     Ok((version_str, maj, min, patch))
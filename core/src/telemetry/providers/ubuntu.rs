@@ -32,7 +32,7 @@ impl TelemetryProvider for Ubuntu {
 
             future::ok(Message::WithoutBody(
                 ResponseResult::Ok(
-                    Response::TelemetryLoad(t.into()))))
+                    Response::TelemetryLoad(Box::new(t.into())))))
         }))
     }
 }
@@ -66,19 +66,19 @@ fn do_load() -> Result<Telemetry> {
 fn version() -> Result<(String, u32, u32, u32)> {
     let out = process::Command::new("lsb_release").arg("-sd").output()?;
     let desc = str::from_utf8(&out.stdout)
-                   .chain_err(|| ErrorKind::SystemCommand("Ubuntu-version"))?;
+                   .chain_err(|| ErrorKind::SystemCommand("Ubuntu-version".into()))?;
 
     let regex = Regex::new(r"([0-9]+)\.([0-9]+)\.([0-9]+)( LTS)?").unwrap();
     if let Some(cap) = regex.captures(&desc) {
-        let version_maj = cap.get(1).unwrap().as_str().parse().chain_err(|| ErrorKind::SystemCommandOutput("lsb_release -sd"))?;
-        let version_min = cap.get(2).unwrap().as_str().parse().chain_err(|| ErrorKind::SystemCommandOutput("lsb_release -sd"))?;
-        let version_patch = cap.get(3).unwrap().as_str().parse().chain_err(|| ErrorKind::SystemCommandOutput("lsb_release -sd"))?;
+        let version_maj = cap.get(1).unwrap().as_str().parse().chain_err(|| ErrorKind::SystemCommandOutput("lsb_release -sd".into()))?;
+        let version_min = cap.get(2).unwrap().as_str().parse().chain_err(|| ErrorKind::SystemCommandOutput("lsb_release -sd".into()))?;
+        let version_patch = cap.get(3).unwrap().as_str().parse().chain_err(|| ErrorKind::SystemCommandOutput("lsb_release -sd".into()))?;
         let mut version_str = format!("{}.{}.{}", version_maj, version_min, version_patch);
         if cap.get(4).is_some() {
             version_str.push_str(" LTS");
         }
         Ok((version_str, version_maj, version_min, version_patch))
     } else {
-        Err(ErrorKind::SystemCommandOutput("lsb_release -sd").into())
+        Err(ErrorKind::SystemCommandOutput("lsb_release -sd".into()).into())
     }
 }